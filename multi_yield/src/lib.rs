@@ -1,11 +1,97 @@
 use anchor_lang::prelude::*;
-use anchor_spl::token::{self, Mint, Token, TokenAccount, MintTo, Transfer};
+use anchor_lang::Discriminator;
+use anchor_lang::solana_program::keccak;
+use anchor_lang::solana_program::instruction::{AccountMeta, Instruction};
+use anchor_lang::solana_program::program::{invoke, invoke_signed};
+use anchor_lang::system_program::{self, CreateAccount};
+use anchor_spl::token_2022::spl_token_2022::{
+    self,
+    extension::{metadata_pointer, transfer_fee, BaseStateWithExtensions, ExtensionType, StateWithExtensions},
+};
+use anchor_spl::token_2022::{self, InitializeMint2, Token2022};
+use anchor_spl::token_interface::{
+    self, Burn, CloseAccount, Mint, MintTo, TokenAccount, TokenInterface, TransferChecked,
+};
+use anchor_spl::metadata::mpl_token_metadata::{self, types::DataV2};
+use anchor_spl::metadata::{
+    create_metadata_accounts_v3, update_metadata_accounts_v2, CreateMetadataAccountsV3, Metadata,
+    MetadataAccount, UpdateMetadataAccountsV2, ID as TOKEN_METADATA_PROGRAM_ID,
+};
 use pyth_sdk_solana::{load_price_feed_from_account_info, PriceFeed, Price};
 use std::convert::TryInto;
 
 // Program ID
 declare_id!("5GFJxKs3qbt6ibwLVJqYDZqoZJoxHe3ShEkZktp5CM3P");
 
+/// Shortest and longest vote-escrow lock (1 week .. 4 years).
+pub const VE_MIN_LOCK: i64 = 7 * 24 * 60 * 60;
+pub const VE_MAX_LOCK: i64 = 4 * 365 * 24 * 60 * 60;
+
+/// How long a governance proposal stays open for votes.
+pub const GOVERNANCE_VOTING_PERIOD: i64 = 3 * 24 * 60 * 60;
+
+/// Least veMY power that must vote on a proposal for it to pass: 1% of the supply cap.
+pub const GOVERNANCE_QUORUM: u64 = MAX_SUPPLY / 100;
+
+/// How long after voting closes a passed proposal can still be executed.
+pub const GOVERNANCE_EXECUTION_WINDOW: i64 = 7 * 24 * 60 * 60;
+
+/// Staking reward rates are annual; rewards accrue per second since the last claim.
+pub const SECONDS_PER_YEAR: i64 = 365 * 24 * 60 * 60;
+
+/// Shortest gap between two crank compounds of the same position.
+pub const CRANK_MIN_INTERVAL: i64 = 24 * 60 * 60;
+
+/// Most NFTs a single staker can have escrowed at once.
+pub const MAX_STAKED_NFTS: usize = 8;
+
+/// Default hard cap on multiYIELD supply: 1B tokens at 6 decimals.
+pub const MAX_SUPPLY: u64 = 1_000_000_000 * 1_000_000;
+
+/// Oldest Pyth price accepted for buybacks and LP valuation, in seconds.
+pub const MAX_PRICE_AGE: u64 = 60;
+
+/// Most LP mints the gauge vote can split emissions across.
+pub const MAX_GAUGES: usize = 10;
+
+/// Virtual stMY shares and multiYIELD backing the liquid pool always holds, so a first
+/// depositor cannot inflate the exchange rate with a donation and round later deposits to zero.
+pub const LIQUID_VIRTUAL_OFFSET: u64 = 1_000_000;
+
+/// Most quote-token fee vaults; every fee claim tracks all of them.
+pub const MAX_FEE_VAULTS: usize = 4;
+
+/// Fixed-point scale for per-share fee accumulators.
+pub const FEE_PRECISION: u128 = 1_000_000_000_000;
+
+/// Fixed-point scale for LP pools' per-weight reward accumulators.
+pub const LP_ACC_PRECISION: u128 = 1_000_000_000_000;
+
+/// Current `GlobalState` size: mint, bump, protocol_wide_volume, authority, unstake_cooldown,
+/// total_staked, max_nft_boost_bps, fee_destination, max_supply, minted (5 x u64), reward_vault,
+/// reward_vault_since, vault_distributed, buyback_amm_program, buyback_price_feed,
+/// buyback_max_slippage_bps, total_burned, governance, dao_treasury, buyback_authority_bump,
+/// legacy_lp_vault, legacy_lp_root, legacy_stake_root.
+pub const GLOBAL_STATE_SPACE: usize =
+    8 + 32 + 1 + 8 + 32 + 8 + 8 + 2 + 32 + 8 + 5 * 8 + 32 + 8 + 8 + 32 + 32 + 2 + 8 + 32 + 32 + 1 + 32 + 32 + 32;
+
+/// Current `StakeAccount` size: owner, amount, stake_timestamp, last_claim_time, delegate.
+pub const STAKE_ACCOUNT_SPACE: usize = 8 + 32 + 8 + 8 + 8 + 32;
+
+/// Current `NFTStakeAccount` size: owner, then up to `MAX_STAKED_NFTS` of (mint, collection,
+/// tier, boost_bps, boost_expires_at).
+pub const NFT_STAKE_SPACE: usize = 8 + 32 + 4 + MAX_STAKED_NFTS * (32 + 32 + 1 + 2 + 8);
+
+/// Sizes of accounts written by the original program, before the layouts above.
+pub const LEGACY_GLOBAL_STATE_SPACE: usize = 8 + 32 + 1 + 8; // mint, bump, protocol_wide_volume
+pub const LEGACY_STAKE_ACCOUNT_SPACE: usize = 8 + 32 + 8 + 8 + 1; // owner, amount, stake_timestamp, auto_compound
+pub const LEGACY_LP_STAKE_SPACE: usize = 8 + 32 + 8 + 1; // owner, lp_staked, reward_multiplier
+pub const LEGACY_NFT_STAKE_SPACE: usize = 8 + 32 + 32 + 1; // owner, nft_minted, boosted
+
+/// `NFTStakeAccount` size from when it escrowed a single NFT: the original fields plus
+/// collection, tier and boost_bps.
+pub const SINGLE_NFT_STAKE_SPACE: usize = LEGACY_NFT_STAKE_SPACE + 32 + 1 + 2;
+
 #[program]
 pub mod multi_yield {
     use super::*;
@@ -13,9 +99,485 @@ pub mod multi_yield {
     /// Initialize the protocol's global state and the multiYIELD mint.
     /// The bump is passed as an argument (instead of referencing ctx.bumps).
     pub fn initialize(ctx: Context<Initialize>, bump: u8) -> Result<()> {
+        init_global_state(
+            &mut ctx.accounts.global_state,
+            &mut ctx.accounts.governance,
+            ctx.accounts.mint.key(),
+            bump,
+            ctx.accounts.user.key(),
+        );
+        Ok(())
+    }
+
+    /// Initialize the protocol with a Token-2022 multiYIELD mint carrying a transfer fee
+    /// (withheld on every transfer, harvested to `fee_destination`) and a metadata pointer,
+    /// which stays empty until `create_token_metadata` points it at the Metaplex metadata.
+    pub fn initialize_token_2022(
+        ctx: Context<InitializeToken2022>,
+        bump: u8,
+        transfer_fee_bps: u16,
+        maximum_fee: u64,
+    ) -> Result<()> {
+        require!(transfer_fee_bps <= 10_000, CustomError::InvalidRewardParameters);
+        let mint_key = ctx.accounts.mint.key();
+        let global_state_key = ctx.accounts.global_state.key();
+        let token_program_id = ctx.accounts.token_program.key();
+
+        let space = ExtensionType::try_calculate_account_len::<spl_token_2022::state::Mint>(&[
+            ExtensionType::TransferFeeConfig,
+            ExtensionType::MetadataPointer,
+        ])?;
+        system_program::create_account(
+            CpiContext::new(
+                ctx.accounts.system_program.to_account_info(),
+                CreateAccount {
+                    from: ctx.accounts.user.to_account_info(),
+                    to: ctx.accounts.mint.to_account_info(),
+                },
+            ),
+            Rent::get()?.minimum_balance(space),
+            space as u64,
+            &token_program_id,
+        )?;
+
+        // Extensions must be initialized before the mint itself
+        invoke(
+            &transfer_fee::instruction::initialize_transfer_fee_config(
+                &token_program_id,
+                &mint_key,
+                Some(&global_state_key),
+                Some(&global_state_key),
+                transfer_fee_bps,
+                maximum_fee,
+            )?,
+            &[ctx.accounts.mint.to_account_info()],
+        )?;
+        invoke(
+            &metadata_pointer::instruction::initialize(
+                &token_program_id,
+                &mint_key,
+                Some(global_state_key),
+                None,
+            )?,
+            &[ctx.accounts.mint.to_account_info()],
+        )?;
+        token_2022::initialize_mint2(
+            CpiContext::new(
+                ctx.accounts.token_program.to_account_info(),
+                InitializeMint2 { mint: ctx.accounts.mint.to_account_info() },
+            ),
+            6,
+            &global_state_key,
+            None,
+        )?;
+
+        init_global_state(
+            &mut ctx.accounts.global_state,
+            &mut ctx.accounts.governance,
+            mint_key,
+            bump,
+            ctx.accounts.user.key(),
+        );
+        Ok(())
+    }
+
+    /// Upgrade authority: resize a global_state written by the original program to the current
+    /// layout, keeping its mint, bump and volume, and create the governance and fee registry PDAs.
+    /// The upgrade authority becomes the protocol admin. `legacy_lp_vault` is the global_state-owned
+    /// token account the original LP stakes were deposited into, for `withdraw_legacy_lp`.
+    pub fn migrate_global_state(ctx: Context<MigrateGlobalState>, legacy_lp_vault: Pubkey) -> Result<()> {
+        let info = ctx.accounts.global_state.to_account_info();
+        require!(info.data_len() == LEGACY_GLOBAL_STATE_SPACE, CustomError::NotLegacyAccount);
+        grow_account(&info, GLOBAL_STATE_SPACE, &ctx.accounts.authority, &ctx.accounts.system_program)?;
+
+        let mut global_state = GlobalState::try_deserialize(&mut &info.try_borrow_data()?[..])?;
+        let (mint, bump) = (global_state.mint, global_state.bump);
+        init_global_state(
+            &mut global_state,
+            &mut ctx.accounts.governance,
+            mint,
+            bump,
+            ctx.accounts.authority.key(),
+        );
+        global_state.legacy_lp_vault = legacy_lp_vault;
+        global_state.try_serialize(&mut &mut info.try_borrow_mut_data()?[..])?;
+        Ok(())
+    }
+
+    /// Admin: commit the merkle root of audited `legacy_balance_leaf(b"legacy_lp", ..)` balances
+    /// that LP stakes from the original program may withdraw, once the legacy LP vault holds
+    /// their total. Their own records are not trusted: the original program took any LP mint
+    /// into any token account.
+    pub fn set_legacy_lp_snapshot(ctx: Context<UpdateGlobalConfig>, root: [u8; 32]) -> Result<()> {
+        ctx.accounts.global_state.legacy_lp_root = root;
+        Ok(())
+    }
+
+    /// Admin: commit the merkle root of audited `legacy_balance_leaf(b"legacy_stake", ..)` balances
+    /// that stake accounts from the original program migrate with, once the staking pool holds
+    /// their total. Their own amounts are not trusted: the original program took stake into any
+    /// token account and auto-compounded it without any transfer.
+    pub fn set_legacy_stake_snapshot(ctx: Context<UpdateGlobalConfig>, root: [u8; 32]) -> Result<()> {
+        ctx.accounts.global_state.legacy_stake_root = root;
+        Ok(())
+    }
+
+    /// Resize a `StakeAccount` written by the original program to the current layout. Its amount
+    /// becomes the owner's `audited_amount`, proven against the admin's legacy stake snapshot.
+    /// The old `auto_compound` flag is dropped (stake positions carry it now), rewards accrue from
+    /// the migration on, and the stake starts earning protocol fees.
+    pub fn migrate_stake_account(
+        ctx: Context<MigrateStakeAccount>,
+        audited_amount: u64,
+        proof: Vec<[u8; 32]>,
+    ) -> Result<()> {
+        let info = ctx.accounts.staker.to_account_info();
+        require!(info.data_len() == LEGACY_STAKE_ACCOUNT_SPACE, CustomError::NotLegacyAccount);
+        grow_account(&info, STAKE_ACCOUNT_SPACE, &ctx.accounts.staker_authority, &ctx.accounts.system_program)?;
+
+        // last_claim_time overlays the old auto_compound byte, so both new fields are rewritten
+        let mut staker = StakeAccount::try_deserialize(&mut &info.try_borrow_data()?[..])?;
+        require_keys_eq!(staker.owner, ctx.accounts.staker_authority.key(), CustomError::Unauthorized);
+        let root = ctx.accounts.global_state.legacy_stake_root;
+        require!(
+            root != [0u8; 32]
+                && proof.len() <= 32
+                && verify_merkle_proof(&proof, root, legacy_balance_leaf(b"legacy_stake", &staker.owner, audited_amount)),
+            CustomError::NotInLegacySnapshot
+        );
+        staker.amount = audited_amount;
+        staker.last_claim_time = Clock::get()?.unix_timestamp;
+        staker.delegate = Pubkey::default();
+        staker.try_serialize(&mut &mut info.try_borrow_mut_data()?[..])?;
+
+        let fee_claim = &mut ctx.accounts.fee_claim;
+        fee_claim.owner = staker.owner;
+        fee_claim.holder = info.key();
+        fee_claim.settle(
+            &ctx.accounts.fee_registry.acc_fee_per_share,
+            &mut ctx.accounts.global_state.total_staked,
+            staker.amount,
+        )
+    }
+
+    /// Rewrite an `NFTStakeAccount` from before NFT stacking into the current layout. An NFT the
+    /// single-NFT layout still holds in escrow is carried over with its boost expired, so it
+    /// counts again after `refresh_nft_boost` and unstakes as usual. The original layout never
+    /// escrowed anything, so it migrates empty.
+    pub fn migrate_nft_stake(ctx: Context<MigrateNFTStake>) -> Result<()> {
+        let info = ctx.accounts.nft_stake.to_account_info();
+        let (owner, staked) = parse_legacy_nft_stake(&info.try_borrow_data()?)?;
+        require_keys_eq!(owner, ctx.accounts.user.key(), CustomError::Unauthorized);
+
+        let mut nfts = Vec::new();
+        if let Some(mut nft) = staked {
+            let escrow = ctx.accounts.nft_escrow.as_ref().ok_or(CustomError::NFTNotStaked)?;
+            let (escrow_key, _) = Pubkey::find_program_address(&[b"nft_escrow", nft.mint.as_ref()], &crate::ID);
+            require!(
+                escrow.key() == escrow_key && escrow.mint == nft.mint && escrow.amount == 1,
+                CustomError::NFTNotStaked
+            );
+            nft.boost_expires_at = Clock::get()?.unix_timestamp;
+            nfts.push(nft);
+        }
+
+        grow_account(&info, NFT_STAKE_SPACE, &ctx.accounts.user, &ctx.accounts.system_program)?;
+        NFTStakeAccount { owner, nfts }.try_serialize(&mut &mut info.try_borrow_mut_data()?[..])?;
+        Ok(())
+    }
+
+    /// Admin: route harvested transfer fees to a token account (e.g. the insurance pool or treasury).
+    pub fn set_fee_destination(ctx: Context<UpdateGlobalConfig>, fee_destination: Pubkey) -> Result<()> {
+        ctx.accounts.global_state.fee_destination = fee_destination;
+        Ok(())
+    }
+
+    /// Admin: set the multiYIELD token account that receives early-exit penalties.
+    pub fn set_dao_treasury(ctx: Context<UpdateGlobalConfig>, dao_treasury: Pubkey) -> Result<()> {
+        ctx.accounts.global_state.dao_treasury = dao_treasury;
+        Ok(())
+    }
+
+    /// Permissionless: sweep withheld transfer fees from the token accounts in `remaining_accounts`
+    /// into the mint, then withdraw everything withheld on the mint to `fee_destination`.
+    pub fn harvest_transfer_fees<'info>(
+        ctx: Context<'_, '_, 'info, 'info, HarvestTransferFees<'info>>,
+    ) -> Result<()> {
+        let token_program_id = ctx.accounts.token_program.key();
+        let mint_key = ctx.accounts.mint.key();
+
+        if !ctx.remaining_accounts.is_empty() {
+            let sources: Vec<&Pubkey> = ctx.remaining_accounts.iter().map(|a| a.key).collect();
+            let mut infos = vec![ctx.accounts.mint.to_account_info()];
+            infos.extend(ctx.remaining_accounts.iter().cloned());
+            invoke(
+                &transfer_fee::instruction::harvest_withheld_tokens_to_mint(&token_program_id, &mint_key, &sources)?,
+                &infos,
+            )?;
+        }
+
+        let seeds = &[b"global_state".as_ref(), &[ctx.accounts.global_state.bump]];
+        let signer = &[&seeds[..]];
+        invoke_signed(
+            &transfer_fee::instruction::withdraw_withheld_tokens_from_mint(
+                &token_program_id,
+                &mint_key,
+                &ctx.accounts.fee_destination.key(),
+                &ctx.accounts.global_state.key(),
+                &[],
+            )?,
+            &[
+                ctx.accounts.mint.to_account_info(),
+                ctx.accounts.fee_destination.to_account_info(),
+                ctx.accounts.global_state.to_account_info(),
+            ],
+            signer,
+        )?;
+        Ok(())
+    }
+
+    /// Admin: cap the combined boost from stacking several staked NFTs.
+    pub fn set_max_nft_boost(ctx: Context<UpdateGlobalConfig>, max_nft_boost_bps: u16) -> Result<()> {
+        require!(max_nft_boost_bps <= 10_000, CustomError::InvalidRewardParameters);
+        ctx.accounts.global_state.max_nft_boost_bps = max_nft_boost_bps;
+        Ok(())
+    }
+
+    /// Admin: lower the supply cap. It can never be raised, nor set below the circulating supply.
+    pub fn set_max_supply(ctx: Context<UpdateGlobalConfigWithMint>, max_supply: u64) -> Result<()> {
+        require!(
+            max_supply <= ctx.accounts.global_state.max_supply && max_supply >= ctx.accounts.mint.supply,
+            CustomError::InvalidRewardParameters
+        );
+        ctx.accounts.global_state.max_supply = max_supply;
+        Ok(())
+    }
+
+    /// Admin: create the staking pool, the one token account backing bonded stake, stake positions
+    /// and veMY locks. Other global_state-owned multiYIELD accounts (the stMY vault, the reward
+    /// vault) can never stand in for it.
+    pub fn initialize_staking_pool(_ctx: Context<InitializeStakingPool>) -> Result<()> {
+        Ok(())
+    }
+
+    /// Admin: switch reward payouts from minting to a program-owned vault funded upfront.
+    /// Once set, no reward path mints new multiYIELD.
+    pub fn initialize_reward_vault(ctx: Context<InitializeRewardVault>) -> Result<()> {
+        let global_state = &mut ctx.accounts.global_state;
+        require!(global_state.reward_vault == Pubkey::default(), CustomError::InvalidRewardVault);
+        global_state.reward_vault = ctx.accounts.reward_vault.key();
+        global_state.reward_vault_since = Clock::get()?.unix_timestamp;
+        Ok(())
+    }
+
+    /// Top up the reward vault from any multiYIELD holder (typically the treasury).
+    pub fn fund_reward_vault(ctx: Context<FundRewardVault>, amount: u64) -> Result<()> {
+        let transfer_accounts = TransferChecked {
+            from: ctx.accounts.funder_token_account.to_account_info(),
+            mint: ctx.accounts.mint.to_account_info(),
+            to: ctx.accounts.reward_vault.to_account_info(),
+            authority: ctx.accounts.funder.to_account_info(),
+        };
+        let transfer_ctx = CpiContext::new(ctx.accounts.token_program.to_account_info(), transfer_accounts);
+        token_interface::transfer_checked(transfer_ctx, amount, ctx.accounts.mint.decimals)?;
+        Ok(())
+    }
+
+    /// View: reward vault balance, total paid out and the runway left at the average payout rate.
+    pub fn reward_vault_runway(ctx: Context<RewardVaultRunway>) -> Result<RewardRunway> {
+        let global_state = &ctx.accounts.global_state;
+        let balance = ctx.accounts.reward_vault.amount;
+        let elapsed = Clock::get()?.unix_timestamp - global_state.reward_vault_since;
+        Ok(RewardRunway {
+            balance,
+            distributed: global_state.vault_distributed,
+            runway_seconds: reward_runway_seconds(balance, global_state.vault_distributed, elapsed),
+        })
+    }
+
+    /// Admin: whitelist the AMM used for buybacks, the Pyth feed (multiYIELD priced in the quote
+    /// token) that bounds its output, and the maximum slippage accepted against that price.
+    pub fn set_buyback_config(
+        ctx: Context<UpdateGlobalConfig>,
+        amm_program: Pubkey,
+        price_feed: Pubkey,
+        max_slippage_bps: u16,
+    ) -> Result<()> {
+        require!(max_slippage_bps <= 10_000, CustomError::InvalidRewardParameters);
         let global_state = &mut ctx.accounts.global_state;
-        global_state.mint = ctx.accounts.mint.key();
-        global_state.bump = bump;
+        global_state.buyback_amm_program = amm_program;
+        global_state.buyback_price_feed = price_feed;
+        global_state.buyback_max_slippage_bps = max_slippage_bps;
+        Ok(())
+    }
+
+    /// Admin: create the vault that accumulates `quote_mint` fees for buybacks, plus the
+    /// program-owned account bought-back multiYIELD lands in before it is burned. Both belong
+    /// to the buyback authority PDA rather than global_state.
+    pub fn create_buyback_vault(ctx: Context<CreateBuybackVault>) -> Result<()> {
+        ctx.accounts.global_state.buyback_authority_bump = ctx.bumps.buyback_authority;
+        Ok(())
+    }
+
+    /// Treasury: swap up to `amount_in` quote tokens from the buyback vault for multiYIELD through
+    /// the whitelisted AMM and burn the proceeds. `swap_data` is the AMM's swap instruction data and
+    /// `remaining_accounts` its accounts; the buyback authority PDA signs as the swap authority, and
+    /// neither global_state nor any token account it owns may be handed to the AMM.
+    pub fn buyback_and_burn<'info>(
+        ctx: Context<'_, '_, 'info, 'info, BuybackAndBurn<'info>>,
+        amount_in: u64,
+        swap_data: Vec<u8>,
+    ) -> Result<()> {
+        require!(
+            ctx.accounts.global_state.buyback_amm_program != Pubkey::default(),
+            CustomError::AmmNotWhitelisted
+        );
+
+        let price = fresh_pyth_price(&ctx.accounts.pyth_price_feed, Clock::get()?.unix_timestamp)?;
+
+        let quote_before = ctx.accounts.buyback_vault.amount;
+        let burn_before = ctx.accounts.burn_account.amount;
+
+        let global_state_key = ctx.accounts.global_state.key();
+        let buyback_authority_key = ctx.accounts.buyback_authority.key();
+        let mut metas = Vec::with_capacity(ctx.remaining_accounts.len());
+        for account in ctx.remaining_accounts {
+            require!(
+                *account.key != global_state_key
+                    && !is_token_account_owned_by(account.owner, &account.try_borrow_data()?, &global_state_key),
+                CustomError::InvalidRemainingAccounts
+            );
+            metas.push(AccountMeta {
+                pubkey: *account.key,
+                is_signer: account.is_signer || *account.key == buyback_authority_key,
+                is_writable: account.is_writable,
+            });
+        }
+        let swap_ix = Instruction {
+            program_id: ctx.accounts.amm_program.key(),
+            accounts: metas,
+            data: swap_data,
+        };
+        let seeds = &[b"buyback_authority".as_ref(), &[ctx.accounts.global_state.buyback_authority_bump]];
+        let signer = &[&seeds[..]];
+        invoke_signed(&swap_ix, ctx.remaining_accounts, signer)?;
+
+        ctx.accounts.buyback_vault.reload()?;
+        ctx.accounts.burn_account.reload()?;
+        let spent = quote_before.saturating_sub(ctx.accounts.buyback_vault.amount);
+        let received = ctx.accounts.burn_account.amount.saturating_sub(burn_before);
+        require!(spent <= amount_in, CustomError::SlippageExceeded);
+
+        let min_out = buyback_min_out(
+            spent,
+            ctx.accounts.quote_mint.decimals,
+            ctx.accounts.mint.decimals,
+            price.price as u64,
+            price.expo,
+            ctx.accounts.global_state.buyback_max_slippage_bps,
+        )
+        .ok_or(CustomError::ArithmeticOverflow)?;
+        require!(received >= min_out, CustomError::SlippageExceeded);
+
+        let cpi_accounts = Burn {
+            mint: ctx.accounts.mint.to_account_info(),
+            from: ctx.accounts.burn_account.to_account_info(),
+            authority: ctx.accounts.buyback_authority.to_account_info(),
+        };
+        let cpi_ctx = CpiContext::new_with_signer(ctx.accounts.token_program.to_account_info(), cpi_accounts, signer);
+        token_interface::burn(cpi_ctx, received)?;
+
+        let global_state = &mut ctx.accounts.global_state;
+        global_state.total_burned = global_state.total_burned.saturating_add(received);
+        Ok(())
+    }
+
+    /// Admin: create Metaplex metadata for the multiYIELD mint so wallets show its name and symbol.
+    /// The global_state PDA signs as mint and update authority. For Token-2022 mints the
+    /// metadata pointer is moved to the new metadata account.
+    pub fn create_token_metadata(
+        ctx: Context<CreateTokenMetadata>,
+        name: String,
+        symbol: String,
+        uri: String,
+    ) -> Result<()> {
+        let seeds = &[b"global_state".as_ref(), &[ctx.accounts.global_state.bump]];
+        let signer = &[&seeds[..]];
+        let cpi_accounts = CreateMetadataAccountsV3 {
+            metadata: ctx.accounts.metadata.to_account_info(),
+            mint: ctx.accounts.mint.to_account_info(),
+            mint_authority: ctx.accounts.global_state.to_account_info(),
+            payer: ctx.accounts.authority.to_account_info(),
+            update_authority: ctx.accounts.global_state.to_account_info(),
+            system_program: ctx.accounts.system_program.to_account_info(),
+            rent: ctx.accounts.rent.to_account_info(),
+        };
+        let cpi_ctx = CpiContext::new_with_signer(
+            ctx.accounts.token_metadata_program.to_account_info(),
+            cpi_accounts,
+            signer,
+        );
+        let data = DataV2 {
+            name,
+            symbol,
+            uri,
+            seller_fee_basis_points: 0,
+            creators: None,
+            collection: None,
+            uses: None,
+        };
+        create_metadata_accounts_v3(cpi_ctx, data, true, true, None)?;
+
+        let mint_info = ctx.accounts.mint.to_account_info();
+        if *mint_info.owner == spl_token_2022::ID {
+            let has_pointer = {
+                let data = mint_info.try_borrow_data()?;
+                StateWithExtensions::<spl_token_2022::state::Mint>::unpack(&data)?
+                    .get_extension::<metadata_pointer::MetadataPointer>()
+                    .is_ok()
+            };
+            if has_pointer {
+                invoke_signed(
+                    &metadata_pointer::instruction::update(
+                        &spl_token_2022::ID,
+                        &mint_info.key(),
+                        &ctx.accounts.global_state.key(),
+                        &[],
+                        Some(ctx.accounts.metadata.key()),
+                    )?,
+                    &[mint_info, ctx.accounts.global_state.to_account_info()],
+                    signer,
+                )?;
+            }
+        }
+        Ok(())
+    }
+
+    /// Admin: point the multiYIELD metadata at a new URI, keeping everything else.
+    pub fn update_token_metadata_uri(ctx: Context<UpdateTokenMetadata>, uri: String) -> Result<()> {
+        let data = metadata_with_uri(&ctx.accounts.metadata, uri);
+
+        let seeds = &[b"global_state".as_ref(), &[ctx.accounts.global_state.bump]];
+        let signer = &[&seeds[..]];
+        let cpi_accounts = UpdateMetadataAccountsV2 {
+            metadata: ctx.accounts.metadata.to_account_info(),
+            update_authority: ctx.accounts.global_state.to_account_info(),
+        };
+        let cpi_ctx = CpiContext::new_with_signer(
+            ctx.accounts.token_metadata_program.to_account_info(),
+            cpi_accounts,
+            signer,
+        );
+        update_metadata_accounts_v2(cpi_ctx, None, Some(data), None, None)
+    }
+
+    /// Admin: set how long unstake tickets wait before they can be claimed.
+    pub fn set_unstake_cooldown(ctx: Context<UpdateGlobalConfig>, cooldown: i64) -> Result<()> {
+        require!(
+            (0..=30 * 24 * 60 * 60).contains(&cooldown),
+            CustomError::InvalidCooldown
+        );
+        ctx.accounts.global_state.unstake_cooldown = cooldown;
         Ok(())
     }
 
@@ -95,515 +657,5559 @@ pub mod multi_yield {
         // Also add some fees to the insurance pool
         if ctx.accounts.insurance_pool_account.to_account_info().key != &Pubkey::default() {
             let fee = reward_amount / 10; // 10% to insurance
-            let seeds = &[b"global_state".as_ref(), &[ctx.accounts.global_state.bump]];
-            let signer = &[&seeds[..]];
-            let cpi_accounts = MintTo {
-                mint: ctx.accounts.mint.to_account_info(),
-                to: ctx.accounts.insurance_pool_account.to_account_info(),
-                authority: ctx.accounts.global_state.to_account_info(),
-            };
-            let cpi_ctx = CpiContext::new_with_signer(ctx.accounts.token_program.to_account_info(), cpi_accounts, signer);
-            token::mint_to(cpi_ctx, fee)?;
+            emit_multi_yield(
+                &mut ctx.accounts.global_state,
+                &mut ctx.accounts.mint,
+                &mut ctx.accounts.reward_vault,
+                ctx.accounts.insurance_pool_account.to_account_info(),
+                &ctx.accounts.token_program,
+                Emission::Insurance,
+                fee,
+            )?;
         }
 
         // Mint remainder to the trader
-        let seeds = &[b"global_state".as_ref(), &[ctx.accounts.global_state.bump]];
-        let signer = &[&seeds[..]];
-        let cpi_accounts = MintTo {
-            mint: ctx.accounts.mint.to_account_info(),
-            to: ctx.accounts.trader_token_account.to_account_info(),
-            authority: ctx.accounts.global_state.to_account_info(),
-        };
-        let cpi_ctx = CpiContext::new_with_signer(ctx.accounts.token_program.to_account_info(), cpi_accounts, signer);
-        token::mint_to(cpi_ctx, reward_amount)?;
-        Ok(())
+        emit_multi_yield(
+            &mut ctx.accounts.global_state,
+            &mut ctx.accounts.mint,
+            &mut ctx.accounts.reward_vault,
+            ctx.accounts.trader_token_account.to_account_info(),
+            &ctx.accounts.token_program,
+            Emission::Trading,
+            reward_amount,
+        )
     }
 
-    /// Stake multiYIELD tokens, with optional auto-compounding and early exit penalty.
-    pub fn stake_tokens(ctx: Context<StakeTokens>, amount: u64, auto_compound: bool) -> Result<()> {
+    /// Bond multiYIELD into the caller's `StakeAccount`. Bonded stake earns boosted rewards paid
+    /// out on claim and a share of protocol fees, and leaves through the unstake cooldown queue.
+    /// Auto-compounding stake goes through `stake_tokens` and the stMY pool instead.
+    pub fn bond_tokens(ctx: Context<BondTokens>, amount: u64) -> Result<()> {
         {
-            let transfer_accounts = Transfer {
+            let transfer_accounts = TransferChecked {
                 from: ctx.accounts.staker_token_account.to_account_info(),
+                mint: ctx.accounts.mint.to_account_info(),
                 to: ctx.accounts.staking_pool_token_account.to_account_info(),
                 authority: ctx.accounts.staker_authority.to_account_info(),
             };
             let transfer_ctx = CpiContext::new(ctx.accounts.token_program.to_account_info(), transfer_accounts);
-            token::transfer(transfer_ctx, amount)?;
+            token_interface::transfer_checked(transfer_ctx, amount, ctx.accounts.mint.decimals)?;
         }
+        // Only credit what reached the pool after any Token-2022 transfer fee
+        let amount = net_of_transfer_fee(&ctx.accounts.mint.to_account_info(), amount)?;
 
         let staker = &mut ctx.accounts.staker;
+        let current_time = Clock::get()?.unix_timestamp;
         staker.owner = ctx.accounts.staker_authority.key();
-        staker.amount = staker.amount.checked_add(amount).unwrap();
-        staker.stake_timestamp = Clock::get()?.unix_timestamp;
-        staker.auto_compound = auto_compound;
+        // Top-ups blend into a weighted-average entry time instead of resetting loyalty
+        staker.stake_timestamp =
+            weighted_stake_timestamp(staker.amount, staker.stake_timestamp, amount, current_time)?;
+        // The new tokens start accruing now without changing what the old ones have earned
+        staker.last_claim_time =
+            weighted_stake_timestamp(staker.amount, staker.last_claim_time, amount, current_time)?;
+        staker.amount = staker.amount.checked_add(amount).ok_or(CustomError::ArithmeticOverflow)?;
+
+        let fee_claim = &mut ctx.accounts.fee_claim;
+        fee_claim.owner = staker.owner;
+        fee_claim.holder = staker.key();
+        fee_claim.settle(
+            &ctx.accounts.fee_registry.acc_fee_per_share,
+            &mut ctx.accounts.global_state.total_staked,
+            staker.amount,
+        )
+    }
+
+    /// Let `delegate` claim this stake's rewards on the owner's behalf (never withdraw).
+    pub fn set_delegate(ctx: Context<SetDelegate>, delegate: Pubkey) -> Result<()> {
+        ctx.accounts.staker.delegate = delegate;
         Ok(())
     }
 
-    /// Claim staking rewards with loyalty multiplier and early exit penalty (10% if < 7 days).
-    pub fn claim_stake_rewards(ctx: Context<ClaimStakeRewards>) -> Result<()> {
+    /// Remove the stake's delegate.
+    pub fn revoke_delegate(ctx: Context<SetDelegate>) -> Result<()> {
+        ctx.accounts.staker.delegate = Pubkey::default();
+        Ok(())
+    }
+
+    /// Claim bonded staking rewards with veMY loyalty boost and early exit penalty (10% if < 7 days).
+    /// Either the owner or their delegate may sign; rewards always go to the owner. NFT boosts need
+    /// their collection floors re-checked through `[collection_config, floor_feed]` pairs in
+    /// `remaining_accounts`.
+    pub fn claim_stake_rewards<'info>(ctx: Context<'_, '_, 'info, 'info, ClaimStakeRewards<'info>>) -> Result<()> {
         let staker = &mut ctx.accounts.staker;
         let current_time = Clock::get()?.unix_timestamp;
         let time_staked = current_time.saturating_sub(staker.stake_timestamp);
 
-        // If <7 days, 10% penalty goes to treasury
-        let min_duration = 7 * 24 * 60 * 60;
-        let penalty_rate = if time_staked < min_duration { 10 } else { 0 };
-
-        // 10% base reward
-        let base_reward = staker.amount / 10;
+        // Continuous loyalty boost from the staker's veMY lock (if any)
+        let voting_power = match &ctx.accounts.ve_lock {
+            Some(ve_lock) => ve_voting_power(ve_lock.amount, ve_lock.lock_end, current_time),
+            None => 0,
+        };
 
-        // loyalty multiplier (over 90 days => extra protocol fees)
-        let loyalty_multiplier: u64 = if time_staked >= 180 * 24 * 60 * 60 {
-            15
-        } else if time_staked >= 90 * 24 * 60 * 60 {
-            13
-        } else if time_staked >= 30 * 24 * 60 * 60 {
-            11
-        } else {
-            10
+        // NFT boosts only count for collections whose floor is re-checked in this claim
+        let floor_scales = nft_floor_scales(ctx.remaining_accounts, current_time)?;
+        let nft_boost_bps = match &ctx.accounts.nft_stake {
+            Some(nft_stake) => nft_stake.active_boost_bps(
+                ctx.accounts.global_state.max_nft_boost_bps,
+                current_time,
+                &floor_scales,
+            ),
+            None => 0,
         };
-        let loyalty_reward = (base_reward * loyalty_multiplier) / 10;
+        let (final_reward, treasury_fee) = stake_reward(
+            staker.amount,
+            time_staked,
+            current_time.saturating_sub(staker.last_claim_time),
+            ctx.accounts.governance.reward_percentage,
+            ve_boost_bps(voting_power, staker.amount),
+            nft_boost_bps,
+        );
 
-        // NFT boost
-        let mut final_reward = loyalty_reward;
-        if ctx.accounts.nft_stake.boosted {
-            final_reward += loyalty_reward / 5;
-        }
+        // Send penalty to DAO treasury
+        emit_multi_yield(
+            &mut ctx.accounts.global_state,
+            &mut ctx.accounts.mint,
+            &mut ctx.accounts.reward_vault,
+            ctx.accounts.dao_treasury_account.to_account_info(),
+            &ctx.accounts.token_program,
+            Emission::Treasury,
+            treasury_fee,
+        )?;
 
-        // If penalty applies
-        let treasury_fee = (final_reward * penalty_rate) / 100;
-        final_reward = final_reward.saturating_sub(treasury_fee);
+        staker.last_claim_time = current_time;
 
-        // Send penalty to DAO treasury
-        if treasury_fee > 0 {
-            let seeds = &[b"global_state".as_ref(), &[ctx.accounts.global_state.bump]];
-            let signer = &[&seeds[..]];
-            let cpi_accounts = MintTo {
-                mint: ctx.accounts.mint.to_account_info(),
-                to: ctx.accounts.dao_treasury_account.to_account_info(),
-                authority: ctx.accounts.global_state.to_account_info(),
-            };
-            let cpi_ctx = CpiContext::new_with_signer(ctx.accounts.token_program.to_account_info(), cpi_accounts, signer);
-            token::mint_to(cpi_ctx, treasury_fee)?;
-        }
+        emit_multi_yield(
+            &mut ctx.accounts.global_state,
+            &mut ctx.accounts.mint,
+            &mut ctx.accounts.reward_vault,
+            ctx.accounts.staker_reward_account.to_account_info(),
+            &ctx.accounts.token_program,
+            Emission::Staking,
+            final_reward,
+        )
+    }
 
-        if staker.auto_compound {
-            staker.amount = staker.amount.saturating_add(final_reward);
-        } else {
-            let seeds = &[b"global_state".as_ref(), &[ctx.accounts.global_state.bump]];
-            let signer = &[&seeds[..]];
-            let cpi_accounts = MintTo {
-                mint: ctx.accounts.mint.to_account_info(),
-                to: ctx.accounts.staker_reward_account.to_account_info(),
-                authority: ctx.accounts.global_state.to_account_info(),
+    /// Permissionless crank: compound rewards for a batch of auto-compounding stake positions.
+    /// `remaining_accounts` starts with `position_count` `[position, nft_stake, fee_claim]` triples;
+    /// pass any non-program account (e.g. the system program) for a position without a linked NFT
+    /// stake. The rest are `[collection_config, floor_feed]` pairs re-checking the floors that NFT
+    /// boosts in the batch depend on. Positions that opted out or were compounded within the last
+    /// day are skipped. The caller earns a 1% tip.
+    pub fn crank_compound<'info>(
+        ctx: Context<'_, '_, 'info, 'info, CrankCompound<'info>>,
+        position_count: u8,
+    ) -> Result<()> {
+        let position_accounts = 3 * position_count as usize;
+        require!(
+            position_count > 0 && ctx.remaining_accounts.len() >= position_accounts,
+            CustomError::InvalidRemainingAccounts
+        );
+        let (positions, floor_accounts) = ctx.remaining_accounts.split_at(position_accounts);
+        let current_time = Clock::get()?.unix_timestamp;
+        let floor_scales = nft_floor_scales(floor_accounts, current_time)?;
+
+        let mut total_compounded: u64 = 0;
+        let mut total_treasury_fee: u64 = 0;
+        let mut total_tip: u64 = 0;
+        let mut total_staked = ctx.accounts.global_state.total_staked;
+        for chunk in positions.chunks_exact(3) {
+            let mut position: Account<StakePosition> = Account::try_from(&chunk[0])?;
+            if !crank_due(&position, current_time) {
+                continue;
+            }
+
+            let nft_boost_bps = match optional_program_account::<NFTStakeAccount>(&chunk[1])? {
+                Some(nft_stake) if nft_stake.key() == position.nft_stake => {
+                    nft_stake.active_boost_bps(ctx.accounts.global_state.max_nft_boost_bps, current_time, &floor_scales)
+                }
+                _ => 0,
             };
-            let cpi_ctx = CpiContext::new_with_signer(
-                ctx.accounts.token_program.to_account_info(),
-                cpi_accounts,
-                signer
+            // A position's own remaining lock acts as its vote-escrow boost
+            let voting_power = ve_voting_power(position.amount, position.lock_until, current_time);
+
+            let time_staked = current_time.saturating_sub(position.stake_timestamp);
+            let (final_reward, treasury_fee) = stake_reward(
+                position.amount,
+                time_staked,
+                current_time.saturating_sub(position.last_claim_time),
+                ctx.accounts.governance.reward_percentage,
+                ve_boost_bps(voting_power, position.amount),
+                nft_boost_bps,
             );
-            token::mint_to(cpi_ctx, final_reward)?;
+            let (compounded, treasury_fee, tip) = crank_split(final_reward, treasury_fee);
+
+            // Netting each share on its own never credits more than the batch transfer delivers
+            let credited = emitted_amount(&ctx.accounts.global_state, &ctx.accounts.mint, compounded)?;
+            position.amount = position.amount.saturating_add(credited);
+            position.last_claim_time = current_time;
+            position.exit(&crate::ID)?;
+
+            let mut fee_claim: Account<FeeClaim> = Account::try_from(&chunk[2])?;
+            require_keys_eq!(fee_claim.holder, position.key(), CustomError::InvalidRemainingAccounts);
+            fee_claim.settle(&ctx.accounts.fee_registry.acc_fee_per_share, &mut total_staked, position.amount)?;
+            fee_claim.exit(&crate::ID)?;
+
+            total_compounded = total_compounded.saturating_add(compounded);
+            total_treasury_fee = total_treasury_fee.saturating_add(treasury_fee);
+            total_tip = total_tip.saturating_add(tip);
+        }
+        ctx.accounts.global_state.total_staked = total_staked;
+
+        for (destination, category, amount) in [
+            (ctx.accounts.staking_pool_token_account.to_account_info(), Emission::Staking, total_compounded),
+            (ctx.accounts.dao_treasury_account.to_account_info(), Emission::Treasury, total_treasury_fee),
+            (ctx.accounts.cranker_token_account.to_account_info(), Emission::Staking, total_tip),
+        ] {
+            emit_multi_yield(
+                &mut ctx.accounts.global_state,
+                &mut ctx.accounts.mint,
+                &mut ctx.accounts.reward_vault,
+                destination,
+                &ctx.accounts.token_program,
+                category,
+                amount,
+            )?;
         }
         Ok(())
     }
 
-    /// Extend `stake_nft()` to check floor price feed for NFT collateral.
-    pub fn stake_nft(ctx: Context<StakeNFT>) -> Result<()> {
-        let nft_stake = &mut ctx.accounts.nft_stake;
-        nft_stake.owner = ctx.accounts.user.key();
-        nft_stake.nft_minted = ctx.accounts.nft_mint.key();
-
-        // use extra price feed for floor checks
-        let floor_feed_info = &ctx.accounts.nft_floor_price_feed;
-        let floor_feed = load_price_feed_from_account_info(floor_feed_info)
-            .map_err(|_| CustomError::OracleError)?;
-        let floor_price_data = floor_feed.get_price_unchecked();
-        require!(floor_price_data.price > 1000, CustomError::NFTFloorTooLow);
+    /// Move `amount` out of the stake into an unbonding ticket. The ticket stops earning staking
+    /// rewards and fees immediately and can be claimed once the protocol cooldown has passed.
+    /// Rewards it accrued so far stay claimable on the remaining stake.
+    pub fn request_unstake(ctx: Context<RequestUnstake>, ticket_id: u64, amount: u64) -> Result<()> {
+        let staker = &mut ctx.accounts.staker;
+        require!(amount > 0 && amount <= staker.amount, CustomError::InsufficientStake);
+        let current_time = Clock::get()?.unix_timestamp;
+        staker.last_claim_time =
+            claim_time_after_withdrawal(staker.amount, staker.last_claim_time, amount, current_time)?;
+        staker.amount -= amount;
+        ctx.accounts.fee_claim.settle(
+            &ctx.accounts.fee_registry.acc_fee_per_share,
+            &mut ctx.accounts.global_state.total_staked,
+            staker.amount,
+        )?;
 
-        nft_stake.boosted = true;
+        let ticket = &mut ctx.accounts.unstake_ticket;
+        ticket.owner = ctx.accounts.staker_authority.key();
+        ticket.ticket_id = ticket_id;
+        ticket.amount = amount;
+        ticket.requested_at = current_time;
+        ticket.claimable_at = current_time + ctx.accounts.global_state.unstake_cooldown;
         Ok(())
     }
 
-    /// Add or remove fees to an insurance pool from trades or external contributions.
-    pub fn insurance_pool_contribution(ctx: Context<InsurancePoolContribution>, amount: u64) -> Result<()> {
-        let transfer_accounts = Transfer {
-            from: ctx.accounts.contributor_token_account.to_account_info(),
-            to: ctx.accounts.insurance_pool_account.to_account_info(),
-            authority: ctx.accounts.contributor_authority.to_account_info(),
+    /// Withdraw a matured unstake ticket from the staking pool and close it.
+    pub fn claim_unstake(ctx: Context<ClaimUnstake>) -> Result<()> {
+        let current_time = Clock::get()?.unix_timestamp;
+        require!(
+            current_time >= ctx.accounts.unstake_ticket.claimable_at,
+            CustomError::CooldownNotElapsed
+        );
+
+        let seeds = &[b"global_state".as_ref(), &[ctx.accounts.global_state.bump]];
+        let signer = &[&seeds[..]];
+        let transfer_accounts = TransferChecked {
+            from: ctx.accounts.staking_pool_token_account.to_account_info(),
+            mint: ctx.accounts.mint.to_account_info(),
+            to: ctx.accounts.staker_token_account.to_account_info(),
+            authority: ctx.accounts.global_state.to_account_info(),
         };
-        let transfer_ctx = CpiContext::new(ctx.accounts.token_program.to_account_info(), transfer_accounts);
-        token::transfer(transfer_ctx, amount)?;
+        let transfer_ctx = CpiContext::new_with_signer(
+            ctx.accounts.token_program.to_account_info(),
+            transfer_accounts,
+            signer
+        );
+        token_interface::transfer_checked(transfer_ctx, ctx.accounts.unstake_ticket.amount, ctx.accounts.mint.decimals)?;
         Ok(())
     }
 
-    /// Stake LP tokens for additional liquidity mining. (unchanged from original)
-    pub fn stake_lp_tokens(ctx: Context<StakeLPTokens>, amount: u64) -> Result<()> {
-        {
-            let transfer_accounts = Transfer {
-                from: ctx.accounts.lp_token_account.to_account_info(),
-                to: ctx.accounts.staking_pool_lp_account.to_account_info(),
-                authority: ctx.accounts.staker_authority.to_account_info(),
-            };
-            let transfer_ctx = CpiContext::new(ctx.accounts.token_program.to_account_info(), transfer_accounts);
-            token::transfer(transfer_ctx, amount)?;
-        }
+    /// Cancel a pending unstake ticket and put its amount back into the stake.
+    /// The returned amount re-enters at the current time for loyalty purposes.
+    pub fn cancel_unstake(ctx: Context<CancelUnstake>) -> Result<()> {
+        let amount = ctx.accounts.unstake_ticket.amount;
+        let current_time = Clock::get()?.unix_timestamp;
+        let staker = &mut ctx.accounts.staker;
+        staker.stake_timestamp =
+            weighted_stake_timestamp(staker.amount, staker.stake_timestamp, amount, current_time)?;
+        staker.last_claim_time =
+            weighted_stake_timestamp(staker.amount, staker.last_claim_time, amount, current_time)?;
+        staker.amount = staker.amount.checked_add(amount).ok_or(CustomError::ArithmeticOverflow)?;
 
-        let lp_stake = &mut ctx.accounts.lp_stake;
-        lp_stake.owner = ctx.accounts.staker_authority.key();
-        lp_stake.lp_staked = lp_stake.lp_staked.checked_add(amount).unwrap();
+        ctx.accounts.fee_claim.settle(
+            &ctx.accounts.fee_registry.acc_fee_per_share,
+            &mut ctx.accounts.global_state.total_staked,
+            staker.amount,
+        )
+    }
 
-        // Tiered multiplier based on total value locked
-        let tvl = lp_stake.lp_staked;
-        lp_stake.reward_multiplier = if tvl > 1_000_000 {
-            5
-        } else if tvl > 100_000 {
-            2
-        } else {
-            1
+    /// Admin: open a real-yield fee vault for a quote mint (e.g. USDC).
+    pub fn create_fee_vault(ctx: Context<CreateFeeVault>) -> Result<()> {
+        let fee_registry = &mut ctx.accounts.fee_registry;
+        require!(
+            (fee_registry.vault_count as usize) < MAX_FEE_VAULTS,
+            CustomError::TooManyFeeVaults
+        );
+        let fee_vault = &mut ctx.accounts.fee_vault;
+        fee_vault.quote_mint = ctx.accounts.quote_mint.key();
+        fee_vault.vault = ctx.accounts.fee_vault_token_account.key();
+        fee_vault.index = fee_registry.vault_count;
+        fee_vault.total_deposited = 0;
+        fee_registry.vault_count += 1;
+        Ok(())
+    }
+
+    /// Deposit protocol fees in a quote token; they are shared pro-rata across all fee-earning
+    /// stake: bonded `StakeAccount`s, stake positions and veMY locks.
+    pub fn deposit_protocol_fees(ctx: Context<DepositProtocolFees>, amount: u64) -> Result<()> {
+        let total_staked = ctx.accounts.global_state.total_staked;
+        require!(total_staked > 0, CustomError::NothingStaked);
+
+        let transfer_accounts = TransferChecked {
+            from: ctx.accounts.depositor_token_account.to_account_info(),
+            mint: ctx.accounts.quote_mint.to_account_info(),
+            to: ctx.accounts.fee_vault_token_account.to_account_info(),
+            authority: ctx.accounts.depositor.to_account_info(),
         };
+        let transfer_ctx = CpiContext::new(ctx.accounts.token_program.to_account_info(), transfer_accounts);
+        token_interface::transfer_checked(transfer_ctx, amount, ctx.accounts.quote_mint.decimals)?;
+        let amount = net_of_transfer_fee(&ctx.accounts.quote_mint.to_account_info(), amount)?;
 
+        let fee_vault = &mut ctx.accounts.fee_vault;
+        let acc = &mut ctx.accounts.fee_registry.acc_fee_per_share[fee_vault.index as usize];
+        *acc = acc
+            .checked_add(fee_per_share_increment(amount, total_staked))
+            .ok_or(CustomError::ArithmeticOverflow)?;
+        fee_vault.total_deposited = fee_vault.total_deposited.saturating_add(amount);
         Ok(())
     }
 
-    /// Claim rewards for staked LP tokens (unchanged).
-    pub fn claim_lp_rewards(ctx: Context<ClaimLPRewards>) -> Result<()> {
-        let lp_stake = &ctx.accounts.lp_stake;
-        let reward = (lp_stake.lp_staked * lp_stake.reward_multiplier as u64) / 100;
+    /// Claim the quote token a fee claim has earned from one fee vault. Fees only accrue to stake
+    /// that was already in place when they were deposited, so a last-moment top-up earns nothing.
+    pub fn claim_fee_rewards(ctx: Context<ClaimFeeRewards>) -> Result<()> {
+        let fee_claim = &mut ctx.accounts.fee_claim;
+        let shares = fee_claim.shares;
+        fee_claim.settle(
+            &ctx.accounts.fee_registry.acc_fee_per_share,
+            &mut ctx.accounts.global_state.total_staked,
+            shares,
+        )?;
+        let owed = std::mem::take(&mut fee_claim.pending[ctx.accounts.fee_vault.index as usize]);
+        if owed == 0 {
+            return Ok(());
+        }
+
         let seeds = &[b"global_state".as_ref(), &[ctx.accounts.global_state.bump]];
         let signer = &[&seeds[..]];
-        let cpi_accounts = MintTo {
-            mint: ctx.accounts.mint.to_account_info(),
-            to: ctx.accounts.lp_reward_account.to_account_info(),
+        let transfer_accounts = TransferChecked {
+            from: ctx.accounts.fee_vault_token_account.to_account_info(),
+            mint: ctx.accounts.quote_mint.to_account_info(),
+            to: ctx.accounts.staker_quote_account.to_account_info(),
             authority: ctx.accounts.global_state.to_account_info(),
         };
-        let cpi_ctx = CpiContext::new_with_signer(
+        let transfer_ctx = CpiContext::new_with_signer(
             ctx.accounts.token_program.to_account_info(),
-            cpi_accounts,
+            transfer_accounts,
             signer
         );
-        token::mint_to(cpi_ctx, reward)?;
+        token_interface::transfer_checked(transfer_ctx, owed, ctx.accounts.quote_mint.decimals)?;
         Ok(())
     }
 
-    /// Governance: update the base reward, LP boost, possibly require a DAO vote.
-    pub fn update_reward_parameters(
-        ctx: Context<UpdateGovernance>,
-        new_reward: u8,
-        new_lp_boost: u8,
+    /// Stake into an indexed position with its own lock period, auto-compound flag and NFT boost.
+    /// Topping up an existing position can only extend its lock.
+    pub fn stake_to_position(
+        ctx: Context<StakeToPosition>,
+        position_id: u64,
+        amount: u64,
+        lock_duration: i64,
+        auto_compound: bool,
     ) -> Result<()> {
-        let governance = &mut ctx.accounts.governance;
-        require!(new_reward <= 50, CustomError::InvalidRewardParameters);
-        require!(new_lp_boost <= 10, CustomError::InvalidRewardParameters);
+        // Locks range from none up to 4 years
+        require!(
+            (0..=4 * 365 * 24 * 60 * 60).contains(&lock_duration),
+            CustomError::InvalidLockDuration
+        );
+
+        {
+            let transfer_accounts = TransferChecked {
+                from: ctx.accounts.staker_token_account.to_account_info(),
+                mint: ctx.accounts.mint.to_account_info(),
+                to: ctx.accounts.staking_pool_token_account.to_account_info(),
+                authority: ctx.accounts.staker_authority.to_account_info(),
+            };
+            let transfer_ctx = CpiContext::new(ctx.accounts.token_program.to_account_info(), transfer_accounts);
+            token_interface::transfer_checked(transfer_ctx, amount, ctx.accounts.mint.decimals)?;
+        }
+        let amount = net_of_transfer_fee(&ctx.accounts.mint.to_account_info(), amount)?;
+
+        let current_time = Clock::get()?.unix_timestamp;
+        let position = &mut ctx.accounts.position;
+        position.owner = ctx.accounts.staker_authority.key();
+        position.position_id = position_id;
+        position.stake_timestamp =
+            weighted_stake_timestamp(position.amount, position.stake_timestamp, amount, current_time)?;
+        position.last_claim_time =
+            weighted_stake_timestamp(position.amount, position.last_claim_time, amount, current_time)?;
+        position.amount = position.amount.checked_add(amount).ok_or(CustomError::ArithmeticOverflow)?;
+        position.lock_until = position.lock_until.max(current_time + lock_duration);
+        position.auto_compound = auto_compound;
+        if let Some(nft_stake) = &ctx.accounts.nft_stake {
+            position.nft_stake = nft_stake.key();
+        }
+
+        let fee_claim = &mut ctx.accounts.fee_claim;
+        fee_claim.owner = position.owner;
+        fee_claim.holder = position.key();
+        fee_claim.settle(
+            &ctx.accounts.fee_registry.acc_fee_per_share,
+            &mut ctx.accounts.global_state.total_staked,
+            position.amount,
+        )
+    }
+
+    /// Claim rewards accrued by a single stake position since its last claim, using the same
+    /// schedule as `claim_stake_rewards`. Auto-compounded rewards are paid into the staking pool
+    /// and the position is credited with what arrives, so it stays fully backed. NFT boosts need
+    /// their collection floors re-checked through `[collection_config, floor_feed]` pairs in
+    /// `remaining_accounts`.
+    pub fn claim_position_rewards<'info>(
+        ctx: Context<'_, '_, 'info, 'info, ClaimPositionRewards<'info>>,
+    ) -> Result<()> {
+        let position = &mut ctx.accounts.position;
+        let current_time = Clock::get()?.unix_timestamp;
+        let time_staked = current_time.saturating_sub(position.stake_timestamp);
+
+        // Boost only applies when the linked NFT stake account is supplied
+        let floor_scales = nft_floor_scales(ctx.remaining_accounts, current_time)?;
+        let nft_boost_bps = match &ctx.accounts.nft_stake {
+            Some(nft_stake) if nft_stake.key() == position.nft_stake => {
+                nft_stake.active_boost_bps(ctx.accounts.global_state.max_nft_boost_bps, current_time, &floor_scales)
+            }
+            _ => 0,
+        };
+        // A position's own remaining lock acts as its vote-escrow boost
+        let voting_power = ve_voting_power(position.amount, position.lock_until, current_time);
+        let (final_reward, treasury_fee) = stake_reward(
+            position.amount,
+            time_staked,
+            current_time.saturating_sub(position.last_claim_time),
+            ctx.accounts.governance.reward_percentage,
+            ve_boost_bps(voting_power, position.amount),
+            nft_boost_bps,
+        );
+        position.last_claim_time = current_time;
+
+        emit_multi_yield(
+            &mut ctx.accounts.global_state,
+            &mut ctx.accounts.mint,
+            &mut ctx.accounts.reward_vault,
+            ctx.accounts.dao_treasury_account.to_account_info(),
+            &ctx.accounts.token_program,
+            Emission::Treasury,
+            treasury_fee,
+        )?;
+
+        let destination = if position.auto_compound {
+            let credited = emitted_amount(&ctx.accounts.global_state, &ctx.accounts.mint, final_reward)?;
+            position.amount = position.amount.saturating_add(credited);
+            ctx.accounts.fee_claim.settle(
+                &ctx.accounts.fee_registry.acc_fee_per_share,
+                &mut ctx.accounts.global_state.total_staked,
+                position.amount,
+            )?;
+            ctx.accounts.staking_pool_token_account.to_account_info()
+        } else {
+            ctx.accounts.staker_reward_account.to_account_info()
+        };
+        emit_multi_yield(
+            &mut ctx.accounts.global_state,
+            &mut ctx.accounts.mint,
+            &mut ctx.accounts.reward_vault,
+            destination,
+            &ctx.accounts.token_program,
+            Emission::Staking,
+            final_reward,
+        )
+    }
+
+    /// Merge `source` into `destination`: amounts add up, entry and last-claim times are weighted
+    /// and the longer lock wins.
+    /// The source position is closed.
+    pub fn merge_stake_positions(ctx: Context<MergeStakePositions>) -> Result<()> {
+        let source = &ctx.accounts.source;
+        let destination = &mut ctx.accounts.destination;
+        require!(
+            source.position_id != destination.position_id,
+            CustomError::SamePosition
+        );
+
+        destination.stake_timestamp = weighted_stake_timestamp(
+            destination.amount,
+            destination.stake_timestamp,
+            source.amount,
+            source.stake_timestamp,
+        )?;
+        destination.last_claim_time = weighted_stake_timestamp(
+            destination.amount,
+            destination.last_claim_time,
+            source.amount,
+            source.last_claim_time,
+        )?;
+        destination.amount = destination
+            .amount
+            .checked_add(source.amount)
+            .ok_or(CustomError::ArithmeticOverflow)?;
+        destination.lock_until = destination.lock_until.max(source.lock_until);
+        if destination.nft_stake == Pubkey::default() {
+            destination.nft_stake = source.nft_stake;
+        }
+
+        // Fees the source earned carry over to the destination's claim
+        let acc_fee_per_share = &ctx.accounts.fee_registry.acc_fee_per_share;
+        let total_staked = &mut ctx.accounts.global_state.total_staked;
+        let source_claim = &mut ctx.accounts.source_fee_claim;
+        source_claim.settle(acc_fee_per_share, total_staked, 0)?;
+        let destination_claim = &mut ctx.accounts.destination_fee_claim;
+        destination_claim.settle(acc_fee_per_share, total_staked, destination.amount)?;
+        for (pending, carried) in destination_claim.pending.iter_mut().zip(source_claim.pending) {
+            *pending = pending.checked_add(carried).ok_or(CustomError::ArithmeticOverflow)?;
+        }
+        Ok(())
+    }
+
+    /// Split `amount` off a position into a new position that inherits its entry and claim times,
+    /// lock and flags.
+    pub fn split_stake_position(
+        ctx: Context<SplitStakePosition>,
+        new_position_id: u64,
+        amount: u64,
+    ) -> Result<()> {
+        let source = &mut ctx.accounts.source;
+        require!(amount > 0 && amount < source.amount, CustomError::InsufficientStake);
+        source.amount -= amount;
+
+        let new_position = &mut ctx.accounts.new_position;
+        new_position.owner = source.owner;
+        new_position.position_id = new_position_id;
+        new_position.amount = amount;
+        new_position.stake_timestamp = source.stake_timestamp;
+        new_position.last_claim_time = source.last_claim_time;
+        new_position.lock_until = source.lock_until;
+        new_position.auto_compound = source.auto_compound;
+        new_position.nft_stake = source.nft_stake;
+
+        let acc_fee_per_share = &ctx.accounts.fee_registry.acc_fee_per_share;
+        let total_staked = &mut ctx.accounts.global_state.total_staked;
+        ctx.accounts.source_fee_claim.settle(acc_fee_per_share, total_staked, source.amount)?;
+        let new_fee_claim = &mut ctx.accounts.new_fee_claim;
+        new_fee_claim.owner = new_position.owner;
+        new_fee_claim.holder = new_position.key();
+        new_fee_claim.settle(acc_fee_per_share, total_staked, amount)
+    }
+
+    /// Withdraw from a stake position once its lock has expired. Rewards the withdrawn tokens
+    /// accrued so far stay claimable on the rest of the position.
+    pub fn withdraw_stake_position(ctx: Context<WithdrawStakePosition>, amount: u64) -> Result<()> {
+        let position = &mut ctx.accounts.position;
+        let current_time = Clock::get()?.unix_timestamp;
+        require!(current_time >= position.lock_until, CustomError::PositionLocked);
+        require!(amount <= position.amount, CustomError::InsufficientStake);
+        position.last_claim_time =
+            claim_time_after_withdrawal(position.amount, position.last_claim_time, amount, current_time)?;
+        position.amount -= amount;
+        ctx.accounts.fee_claim.settle(
+            &ctx.accounts.fee_registry.acc_fee_per_share,
+            &mut ctx.accounts.global_state.total_staked,
+            position.amount,
+        )?;
+
+        let seeds = &[b"global_state".as_ref(), &[ctx.accounts.global_state.bump]];
+        let signer = &[&seeds[..]];
+        let transfer_accounts = TransferChecked {
+            from: ctx.accounts.staking_pool_token_account.to_account_info(),
+            mint: ctx.accounts.mint.to_account_info(),
+            to: ctx.accounts.staker_token_account.to_account_info(),
+            authority: ctx.accounts.global_state.to_account_info(),
+        };
+        let transfer_ctx = CpiContext::new_with_signer(
+            ctx.accounts.token_program.to_account_info(),
+            transfer_accounts,
+            signer
+        );
+        token_interface::transfer_checked(transfer_ctx, amount, ctx.accounts.mint.decimals)?;
+        Ok(())
+    }
+
+    /// Lock multiYIELD for 1 week to 4 years in exchange for linearly decaying veMY voting/boost power.
+    pub fn create_ve_lock(ctx: Context<CreateVeLock>, amount: u64, lock_duration: i64) -> Result<()> {
+        require!(amount > 0, CustomError::InsufficientStake);
+        require!(
+            (VE_MIN_LOCK..=VE_MAX_LOCK).contains(&lock_duration),
+            CustomError::InvalidLockDuration
+        );
+
+        let transfer_accounts = TransferChecked {
+            from: ctx.accounts.staker_token_account.to_account_info(),
+            mint: ctx.accounts.mint.to_account_info(),
+            to: ctx.accounts.staking_pool_token_account.to_account_info(),
+            authority: ctx.accounts.staker_authority.to_account_info(),
+        };
+        let transfer_ctx = CpiContext::new(ctx.accounts.token_program.to_account_info(), transfer_accounts);
+        token_interface::transfer_checked(transfer_ctx, amount, ctx.accounts.mint.decimals)?;
+        let amount = net_of_transfer_fee(&ctx.accounts.mint.to_account_info(), amount)?;
+
+        let current_time = Clock::get()?.unix_timestamp;
+        let ve_lock = &mut ctx.accounts.ve_lock;
+        ve_lock.owner = ctx.accounts.staker_authority.key();
+        ve_lock.amount = amount;
+        ve_lock.lock_start = current_time;
+        ve_lock.lock_end = current_time + lock_duration;
+
+        let fee_claim = &mut ctx.accounts.fee_claim;
+        fee_claim.owner = ve_lock.owner;
+        fee_claim.holder = ve_lock.key();
+        fee_claim.settle(
+            &ctx.accounts.fee_registry.acc_fee_per_share,
+            &mut ctx.accounts.global_state.total_staked,
+            ve_lock.amount,
+        )
+    }
+
+    /// Add more multiYIELD to an active veMY lock without changing its end.
+    pub fn increase_ve_lock_amount(ctx: Context<UpdateVeLock>, amount: u64) -> Result<()> {
+        let current_time = Clock::get()?.unix_timestamp;
+        require!(current_time < ctx.accounts.ve_lock.lock_end, CustomError::LockExpired);
+
+        let transfer_accounts = TransferChecked {
+            from: ctx.accounts.staker_token_account.to_account_info(),
+            mint: ctx.accounts.mint.to_account_info(),
+            to: ctx.accounts.staking_pool_token_account.to_account_info(),
+            authority: ctx.accounts.staker_authority.to_account_info(),
+        };
+        let transfer_ctx = CpiContext::new(ctx.accounts.token_program.to_account_info(), transfer_accounts);
+        token_interface::transfer_checked(transfer_ctx, amount, ctx.accounts.mint.decimals)?;
+        let amount = net_of_transfer_fee(&ctx.accounts.mint.to_account_info(), amount)?;
+
+        let ve_lock = &mut ctx.accounts.ve_lock;
+        ve_lock.amount = ve_lock.amount.checked_add(amount).ok_or(CustomError::ArithmeticOverflow)?;
+        ctx.accounts.fee_claim.settle(
+            &ctx.accounts.fee_registry.acc_fee_per_share,
+            &mut ctx.accounts.global_state.total_staked,
+            ve_lock.amount,
+        )
+    }
+
+    /// Push a veMY lock's end out to `now + lock_duration` (never shorter, at most 4 years).
+    pub fn extend_ve_lock(ctx: Context<ExtendVeLock>, lock_duration: i64) -> Result<()> {
+        require!(
+            (VE_MIN_LOCK..=VE_MAX_LOCK).contains(&lock_duration),
+            CustomError::InvalidLockDuration
+        );
+        let current_time = Clock::get()?.unix_timestamp;
+        let ve_lock = &mut ctx.accounts.ve_lock;
+        let new_end = current_time + lock_duration;
+        require!(new_end > ve_lock.lock_end, CustomError::InvalidLockDuration);
+        ve_lock.lock_end = new_end;
+        Ok(())
+    }
+
+    /// Withdraw an expired veMY lock and close it. Fees it earned stay claimable.
+    pub fn withdraw_ve_lock(ctx: Context<WithdrawVeLock>) -> Result<()> {
+        let current_time = Clock::get()?.unix_timestamp;
+        require!(current_time >= ctx.accounts.ve_lock.lock_end, CustomError::PositionLocked);
+        ctx.accounts.fee_claim.settle(
+            &ctx.accounts.fee_registry.acc_fee_per_share,
+            &mut ctx.accounts.global_state.total_staked,
+            0,
+        )?;
+
+        let seeds = &[b"global_state".as_ref(), &[ctx.accounts.global_state.bump]];
+        let signer = &[&seeds[..]];
+        let transfer_accounts = TransferChecked {
+            from: ctx.accounts.staking_pool_token_account.to_account_info(),
+            mint: ctx.accounts.mint.to_account_info(),
+            to: ctx.accounts.staker_token_account.to_account_info(),
+            authority: ctx.accounts.global_state.to_account_info(),
+        };
+        let transfer_ctx = CpiContext::new_with_signer(
+            ctx.accounts.token_program.to_account_info(),
+            transfer_accounts,
+            signer
+        );
+        token_interface::transfer_checked(transfer_ctx, ctx.accounts.ve_lock.amount, ctx.accounts.mint.decimals)?;
+        Ok(())
+    }
+
+    /// Open a governance proposal for veMY voting. Only holders of a live veMY lock may propose.
+    /// `action_hash` is the `governance_action_hash` of the one change the proposal authorizes.
+    pub fn create_governance_proposal(
+        ctx: Context<CreateGovernanceProposal>,
+        proposal_id: u64,
+        action_hash: [u8; 32],
+    ) -> Result<()> {
+        let current_time = Clock::get()?.unix_timestamp;
+        let ve_lock = &ctx.accounts.ve_lock;
+        require!(
+            ve_voting_power(ve_lock.amount, ve_lock.lock_end, current_time) > 0,
+            CustomError::LockExpired
+        );
+
+        let proposal = &mut ctx.accounts.proposal;
+        proposal.proposal_id = proposal_id;
+        proposal.proposer = ctx.accounts.proposer.key();
+        proposal.voting_ends_at = current_time + GOVERNANCE_VOTING_PERIOD;
+        proposal.action_hash = action_hash;
+        Ok(())
+    }
+
+    /// Vote for or against an open proposal with the caller's current veMY voting power.
+    /// The vote record is a per-proposal receipt, so each voter votes once per proposal.
+    pub fn cast_governance_vote(ctx: Context<CastGovernanceVote>, support: bool) -> Result<()> {
+        let current_time = Clock::get()?.unix_timestamp;
+        require!(current_time < ctx.accounts.proposal.voting_ends_at, CustomError::VotingClosed);
+        let ve_lock = &ctx.accounts.ve_lock;
+        let weight = ve_voting_power(ve_lock.amount, ve_lock.lock_end, current_time);
+        require!(weight > 0, CustomError::LockExpired);
+
+        let vote_record = &mut ctx.accounts.vote_record;
+        vote_record.voter = ctx.accounts.voter.key();
+        vote_record.proposal = ctx.accounts.proposal.key();
+        vote_record.weight = weight;
+        vote_record.support = support;
+
+        let proposal = &mut ctx.accounts.proposal;
+        if support {
+            proposal.yes_votes = proposal.yes_votes.saturating_add(weight);
+        } else {
+            proposal.no_votes = proposal.no_votes.saturating_add(weight);
+        }
+        Ok(())
+    }
+
+    /// Tally a proposal once voting has closed. A passed proposal lets the admin execute its
+    /// action once, within `GOVERNANCE_EXECUTION_WINDOW`.
+    pub fn finalize_governance_proposal(ctx: Context<FinalizeGovernanceProposal>) -> Result<()> {
+        let current_time = Clock::get()?.unix_timestamp;
+        let proposal = &mut ctx.accounts.proposal;
+        require!(current_time >= proposal.voting_ends_at, CustomError::VotingOpen);
+        require!(!proposal.finalized, CustomError::ProposalFinalized);
+
+        proposal.finalized = true;
+        proposal.passed = proposal_passed(proposal.yes_votes, proposal.no_votes);
+        Ok(())
+    }
+
+    /// Admin: set up the liquid staking pool, its stMY receipt mint and its vault.
+    pub fn initialize_liquid_staking(ctx: Context<InitializeLiquidStaking>, reward_rate_bps: u16) -> Result<()> {
+        require!(reward_rate_bps <= 5_000, CustomError::InvalidRewardParameters);
+        let liquid_pool = &mut ctx.accounts.liquid_pool;
+        liquid_pool.st_mint = ctx.accounts.st_mint.key();
+        liquid_pool.vault = ctx.accounts.liquid_vault.key();
+        liquid_pool.reward_rate_bps = reward_rate_bps;
+        liquid_pool.last_accrual = Clock::get()?.unix_timestamp;
+        Ok(())
+    }
+
+    /// Stake multiYIELD into the pooled staking vault and receive transferable stMY receipts.
+    /// Rewards compound into the vault for every holder, so there is no per-account `auto_compound`.
+    pub fn stake_tokens(ctx: Context<LiquidStake>, amount: u64) -> Result<()> {
+        let current_time = Clock::get()?.unix_timestamp;
+        let accounts = ctx.accounts;
+        accrue_liquid_pool(
+            &mut accounts.liquid_pool,
+            &mut accounts.liquid_vault,
+            &mut accounts.mint,
+            &mut accounts.global_state,
+            &mut accounts.reward_vault,
+            &accounts.token_program,
+            current_time,
+        )?;
+
+        let received = net_of_transfer_fee(&accounts.mint.to_account_info(), amount)?;
+        let shares = liquid_shares_for_deposit(received, accounts.liquid_vault.amount, accounts.st_mint.supply);
+        require!(shares > 0, CustomError::InsufficientStake);
+
+        let transfer_accounts = TransferChecked {
+            from: accounts.staker_token_account.to_account_info(),
+            mint: accounts.mint.to_account_info(),
+            to: accounts.liquid_vault.to_account_info(),
+            authority: accounts.staker_authority.to_account_info(),
+        };
+        let transfer_ctx = CpiContext::new(accounts.token_program.to_account_info(), transfer_accounts);
+        token_interface::transfer_checked(transfer_ctx, amount, accounts.mint.decimals)?;
+
+        let seeds = &[b"global_state".as_ref(), &[accounts.global_state.bump]];
+        let signer = &[&seeds[..]];
+        let cpi_accounts = MintTo {
+            mint: accounts.st_mint.to_account_info(),
+            to: accounts.staker_st_account.to_account_info(),
+            authority: accounts.global_state.to_account_info(),
+        };
+        let cpi_ctx = CpiContext::new_with_signer(accounts.token_program.to_account_info(), cpi_accounts, signer);
+        token_interface::mint_to(cpi_ctx, shares)?;
+        Ok(())
+    }
+
+    /// Burn stMY receipts and withdraw the underlying multiYIELD at the current exchange rate.
+    pub fn unstake_tokens(ctx: Context<LiquidStake>, shares: u64) -> Result<()> {
+        let current_time = Clock::get()?.unix_timestamp;
+        let accounts = ctx.accounts;
+        accrue_liquid_pool(
+            &mut accounts.liquid_pool,
+            &mut accounts.liquid_vault,
+            &mut accounts.mint,
+            &mut accounts.global_state,
+            &mut accounts.reward_vault,
+            &accounts.token_program,
+            current_time,
+        )?;
+
+        let amount = liquid_underlying_for_shares(shares, accounts.liquid_vault.amount, accounts.st_mint.supply);
+        require!(amount > 0, CustomError::InsufficientStake);
+
+        let burn_accounts = Burn {
+            mint: accounts.st_mint.to_account_info(),
+            from: accounts.staker_st_account.to_account_info(),
+            authority: accounts.staker_authority.to_account_info(),
+        };
+        let burn_ctx = CpiContext::new(accounts.token_program.to_account_info(), burn_accounts);
+        token_interface::burn(burn_ctx, shares)?;
+
+        let seeds = &[b"global_state".as_ref(), &[accounts.global_state.bump]];
+        let signer = &[&seeds[..]];
+        let transfer_accounts = TransferChecked {
+            from: accounts.liquid_vault.to_account_info(),
+            mint: accounts.mint.to_account_info(),
+            to: accounts.staker_token_account.to_account_info(),
+            authority: accounts.global_state.to_account_info(),
+        };
+        let transfer_ctx = CpiContext::new_with_signer(
+            accounts.token_program.to_account_info(),
+            transfer_accounts,
+            signer
+        );
+        token_interface::transfer_checked(transfer_ctx, amount, accounts.mint.decimals)?;
+        Ok(())
+    }
+
+    /// Permissionless: mint accrued rewards into the liquid vault, raising the stMY exchange rate.
+    pub fn accrue_liquid_rewards(ctx: Context<AccrueLiquidRewards>) -> Result<()> {
+        let current_time = Clock::get()?.unix_timestamp;
+        let accounts = ctx.accounts;
+        accrue_liquid_pool(
+            &mut accounts.liquid_pool,
+            &mut accounts.liquid_vault,
+            &mut accounts.mint,
+            &mut accounts.global_state,
+            &mut accounts.reward_vault,
+            &accounts.token_program,
+            current_time,
+        )
+    }
+
+    /// Extend `stake_nft()` to check floor price feed for NFT collateral.
+    /// The NFT is moved into a protocol-owned escrow for as long as the boost is active.
+    /// A rarity `tier` above 0 must be co-signed by the collection's tier authority or
+    /// proven with a merkle `proof` of (mint, tier) against the collection's tier root.
+    pub fn stake_nft(ctx: Context<StakeNFT>, tier: u8, proof: Vec<[u8; 32]>) -> Result<()> {
+        require!(
+            ctx.accounts.nft_stake.nfts.len() < MAX_STAKED_NFTS,
+            CustomError::NFTLimitReached
+        );
+
+        let transfer_accounts = TransferChecked {
+            from: ctx.accounts.user_nft_account.to_account_info(),
+            mint: ctx.accounts.nft_mint.to_account_info(),
+            to: ctx.accounts.nft_escrow.to_account_info(),
+            authority: ctx.accounts.user.to_account_info(),
+        };
+        let transfer_ctx = CpiContext::new(ctx.accounts.token_program.to_account_info(), transfer_accounts);
+        token_interface::transfer_checked(transfer_ctx, 1, ctx.accounts.nft_mint.decimals)?;
+
+        // Only NFTs from a verified, allowlisted collection can be staked
+        let collection_config = &ctx.accounts.collection_config;
+        let metadata_data = ctx.accounts.nft_metadata.try_borrow_data()?;
+        let (metadata_mint, verified_collection) = parse_metadata_collection(&metadata_data)?;
+        require_keys_eq!(metadata_mint, ctx.accounts.nft_mint.key(), CustomError::InvalidNFTMetadata);
+        require!(
+            verified_collection == Some(collection_config.collection_mint),
+            CustomError::CollectionNotAllowed
+        );
+
+        // use the collection's own price feed for floor checks
+        let current_time = Clock::get()?.unix_timestamp;
+        let floor_price = fresh_pyth_price(&ctx.accounts.nft_floor_price_feed, current_time)?;
+        require!(
+            floor_price.price > collection_config.min_floor_price,
+            CustomError::NFTFloorTooLow
+        );
+
+        // Trait/rarity tier bonus on top of the collection's base boost
+        let mut boost_bps = collection_config.boost_bps;
+        if tier > 0 {
+            require!(
+                tier as usize <= collection_config.tier_boost_bps.len() && proof.len() <= 32,
+                CustomError::InvalidNFTTier
+            );
+            let authority_signed = match &ctx.accounts.tier_authority {
+                Some(signer) => {
+                    collection_config.tier_authority != Pubkey::default()
+                        && signer.key() == collection_config.tier_authority
+                }
+                None => false,
+            };
+            let proven = collection_config.tier_merkle_root != [0u8; 32]
+                && verify_merkle_proof(
+                    &proof,
+                    collection_config.tier_merkle_root,
+                    nft_tier_leaf(&ctx.accounts.nft_mint.key(), tier),
+                );
+            require!(authority_signed || proven, CustomError::InvalidNFTTier);
+            boost_bps = boost_bps.saturating_add(collection_config.tier_boost_bps[tier as usize - 1]);
+        }
+
+        let nft_stake = &mut ctx.accounts.nft_stake;
+        nft_stake.owner = ctx.accounts.user.key();
+        nft_stake.nfts.push(StakedNft {
+            mint: ctx.accounts.nft_mint.key(),
+            collection: collection_config.collection_mint,
+            tier,
+            boost_bps,
+            boost_expires_at: boost_expiry(current_time, collection_config.boost_duration),
+        });
+        Ok(())
+    }
+
+    /// Return an escrowed NFT to its owner and drop its boost.
+    pub fn unstake_nft(ctx: Context<UnstakeNFT>) -> Result<()> {
+        ctx.accounts.nft_stake.remove_nft(&ctx.accounts.nft_mint.key())?;
+
+        let seeds = &[b"global_state".as_ref(), &[ctx.accounts.global_state.bump]];
+        let signer = &[&seeds[..]];
+
+        let transfer_accounts = TransferChecked {
+            from: ctx.accounts.nft_escrow.to_account_info(),
+            mint: ctx.accounts.nft_mint.to_account_info(),
+            to: ctx.accounts.user_nft_account.to_account_info(),
+            authority: ctx.accounts.global_state.to_account_info(),
+        };
+        let transfer_ctx = CpiContext::new_with_signer(
+            ctx.accounts.token_program.to_account_info(),
+            transfer_accounts,
+            signer
+        );
+        token_interface::transfer_checked(transfer_ctx, 1, ctx.accounts.nft_mint.decimals)?;
+
+        let close_accounts = CloseAccount {
+            account: ctx.accounts.nft_escrow.to_account_info(),
+            destination: ctx.accounts.user.to_account_info(),
+            authority: ctx.accounts.global_state.to_account_info(),
+        };
+        let close_ctx = CpiContext::new_with_signer(
+            ctx.accounts.token_program.to_account_info(),
+            close_accounts,
+            signer
+        );
+        token_interface::close_account(close_ctx)?;
+        Ok(())
+    }
+
+    /// Re-check a staked NFT's collection floor and restart its boost expiry.
+    pub fn refresh_nft_boost(ctx: Context<RefreshNFTBoost>, nft_mint: Pubkey) -> Result<()> {
+        let collection_config = &ctx.accounts.collection_config;
+        let current_time = Clock::get()?.unix_timestamp;
+        let floor_price = fresh_pyth_price(&ctx.accounts.nft_floor_price_feed, current_time)?;
+        require!(
+            floor_price.price > collection_config.min_floor_price,
+            CustomError::NFTFloorTooLow
+        );
+
+        let nft = ctx
+            .accounts
+            .nft_stake
+            .nfts
+            .iter_mut()
+            .find(|nft| nft.mint == nft_mint)
+            .ok_or(CustomError::NFTNotStaked)?;
+        require_keys_eq!(nft.collection, collection_config.collection_mint, CustomError::CollectionNotAllowed);
+        nft.boost_expires_at = boost_expiry(current_time, collection_config.boost_duration);
+        Ok(())
+    }
+
+    /// Add or remove fees to an insurance pool from trades or external contributions.
+    pub fn insurance_pool_contribution(ctx: Context<InsurancePoolContribution>, amount: u64) -> Result<()> {
+        let transfer_accounts = TransferChecked {
+            from: ctx.accounts.contributor_token_account.to_account_info(),
+            mint: ctx.accounts.mint.to_account_info(),
+            to: ctx.accounts.insurance_pool_account.to_account_info(),
+            authority: ctx.accounts.contributor_authority.to_account_info(),
+        };
+        let transfer_ctx = CpiContext::new(ctx.accounts.token_program.to_account_info(), transfer_accounts);
+        token_interface::transfer_checked(transfer_ctx, amount, ctx.accounts.mint.decimals)?;
+        Ok(())
+    }
+
+    /// Stake LP tokens into the pool for `lp_mint`. Rewards accrued so far are settled first, so
+    /// a top-up only earns from now on. The tier multiplier is priced here; `reprice_lp_stake`
+    /// keeps it current afterwards.
+    pub fn stake_lp_tokens(ctx: Context<StakeLPTokens>, amount: u64) -> Result<()> {
+        let current_time = Clock::get()?.unix_timestamp;
+        update_lp_pool(&mut ctx.accounts.lp_pool, &ctx.accounts.lp_controller, current_time);
+        settle_lp_rewards(&mut ctx.accounts.lp_stake, ctx.accounts.lp_pool.acc_reward_per_share)?;
+
+        {
+            let transfer_accounts = TransferChecked {
+                from: ctx.accounts.lp_token_account.to_account_info(),
+                mint: ctx.accounts.lp_mint.to_account_info(),
+                to: ctx.accounts.staking_pool_lp_account.to_account_info(),
+                authority: ctx.accounts.staker_authority.to_account_info(),
+            };
+            let transfer_ctx = CpiContext::new(ctx.accounts.token_program.to_account_info(), transfer_accounts);
+            token_interface::transfer_checked(transfer_ctx, amount, ctx.accounts.lp_mint.decimals)?;
+        }
+        let amount = net_of_transfer_fee(&ctx.accounts.lp_mint.to_account_info(), amount)?;
+
+        let lp_stake = &mut ctx.accounts.lp_stake;
+        let lp_pool = &mut ctx.accounts.lp_pool;
+        let old_weight = lp_stake.weight();
+        lp_stake.owner = ctx.accounts.staker_authority.key();
+        lp_stake.lp_mint = lp_pool.lp_mint;
+        lp_stake.lp_staked = lp_stake.lp_staked.checked_add(amount).ok_or(CustomError::ArithmeticOverflow)?;
+        let usd_value = lp_usd_value(
+            lp_pool,
+            lp_stake.lp_staked,
+            ctx.accounts.lp_mint.supply,
+            &ctx.accounts.reserve_a,
+            &ctx.accounts.reserve_b,
+            &ctx.accounts.price_feed_a,
+            &ctx.accounts.price_feed_b,
+            current_time,
+        )?;
+        reweight_lp_stake(lp_stake, lp_pool, old_weight, usd_value)
+    }
+
+    /// Withdraw staked LP tokens from the LP vault. Rewards earned so far are settled first and
+    /// stay claimable; the tier multiplier is recomputed for what remains.
+    pub fn unstake_lp_tokens(ctx: Context<UnstakeLPTokens>, amount: u64) -> Result<()> {
+        let current_time = Clock::get()?.unix_timestamp;
+        update_lp_pool(&mut ctx.accounts.lp_pool, &ctx.accounts.lp_controller, current_time);
+        let lp_stake = &mut ctx.accounts.lp_stake;
+        let lp_pool = &mut ctx.accounts.lp_pool;
+        settle_lp_rewards(lp_stake, lp_pool.acc_reward_per_share)?;
+        require!(amount > 0 && amount <= lp_stake.lp_staked, CustomError::InsufficientStake);
+
+        let old_weight = lp_stake.weight();
+        lp_stake.lp_staked -= amount;
+        let usd_value = lp_usd_value(
+            lp_pool,
+            lp_stake.lp_staked,
+            ctx.accounts.lp_mint.supply,
+            &ctx.accounts.reserve_a,
+            &ctx.accounts.reserve_b,
+            &ctx.accounts.price_feed_a,
+            &ctx.accounts.price_feed_b,
+            current_time,
+        )?;
+        reweight_lp_stake(lp_stake, lp_pool, old_weight, usd_value)?;
+
+        let seeds = &[b"global_state".as_ref(), &[ctx.accounts.global_state.bump]];
+        let signer = &[&seeds[..]];
+        let transfer_accounts = TransferChecked {
+            from: ctx.accounts.lp_vault.to_account_info(),
+            mint: ctx.accounts.lp_mint.to_account_info(),
+            to: ctx.accounts.lp_token_account.to_account_info(),
+            authority: ctx.accounts.global_state.to_account_info(),
+        };
+        let transfer_ctx = CpiContext::new_with_signer(ctx.accounts.token_program.to_account_info(), transfer_accounts, signer);
+        token_interface::transfer_checked(transfer_ctx, amount, ctx.accounts.lp_mint.decimals)?;
+        Ok(())
+    }
+
+    /// Reprice an LP stake's tier multiplier from the pool's current reserves and Pyth prices.
+    /// Permissionless, so a stake priced while its LP tokens were worth more cannot keep the
+    /// boosted weight once they are not. Rewards earned at the old weight are settled first.
+    pub fn reprice_lp_stake(ctx: Context<RepriceLpStake>) -> Result<()> {
+        let current_time = Clock::get()?.unix_timestamp;
+        update_lp_pool(&mut ctx.accounts.lp_pool, &ctx.accounts.lp_controller, current_time);
+        let lp_stake = &mut ctx.accounts.lp_stake;
+        let lp_pool = &mut ctx.accounts.lp_pool;
+        settle_lp_rewards(lp_stake, lp_pool.acc_reward_per_share)?;
+
+        let old_weight = lp_stake.weight();
+        let usd_value = lp_usd_value(
+            lp_pool,
+            lp_stake.lp_staked,
+            ctx.accounts.lp_mint.supply,
+            &ctx.accounts.reserve_a,
+            &ctx.accounts.reserve_b,
+            &ctx.accounts.price_feed_a,
+            &ctx.accounts.price_feed_b,
+            current_time,
+        )?;
+        reweight_lp_stake(lp_stake, lp_pool, old_weight, usd_value)
+    }
+
+    /// Withdraw an LP stake made with the original program (one per owner, before per-mint LP
+    /// pools) from the legacy LP vault and close it. It pays the owner's `audited_amount`, proven
+    /// against the admin's legacy LP snapshot, never the stake's own record. It earns nothing
+    /// further; stake into an LP pool to keep earning.
+    pub fn withdraw_legacy_lp(
+        ctx: Context<WithdrawLegacyLp>,
+        audited_amount: u64,
+        proof: Vec<[u8; 32]>,
+    ) -> Result<()> {
+        let info = ctx.accounts.legacy_lp_stake.to_account_info();
+        let (owner, _) = parse_legacy_lp_stake(&info.try_borrow_data()?)?;
+        require_keys_eq!(owner, ctx.accounts.staker_authority.key(), CustomError::Unauthorized);
+        let root = ctx.accounts.global_state.legacy_lp_root;
+        require!(
+            root != [0u8; 32]
+                && proof.len() <= 32
+                && verify_merkle_proof(&proof, root, legacy_balance_leaf(b"legacy_lp", &owner, audited_amount)),
+            CustomError::NotInLegacySnapshot
+        );
+
+        let seeds = &[b"global_state".as_ref(), &[ctx.accounts.global_state.bump]];
+        let signer = &[&seeds[..]];
+        let transfer_accounts = TransferChecked {
+            from: ctx.accounts.legacy_lp_vault.to_account_info(),
+            mint: ctx.accounts.lp_mint.to_account_info(),
+            to: ctx.accounts.lp_token_account.to_account_info(),
+            authority: ctx.accounts.global_state.to_account_info(),
+        };
+        let transfer_ctx = CpiContext::new_with_signer(ctx.accounts.token_program.to_account_info(), transfer_accounts, signer);
+        token_interface::transfer_checked(transfer_ctx, audited_amount, ctx.accounts.lp_mint.decimals)?;
+
+        close_program_account(&info, &ctx.accounts.staker_authority.to_account_info())
+    }
+
+    /// Claim LP rewards accrued since the last claim. Rewards stream at the pool's rate and are
+    /// split by stake weight over time, so repeated calls only pay what has accrued in between.
+    pub fn claim_lp_rewards(ctx: Context<ClaimLPRewards>) -> Result<()> {
+        let current_time = Clock::get()?.unix_timestamp;
+        update_lp_pool(&mut ctx.accounts.lp_pool, &ctx.accounts.lp_controller, current_time);
+        let lp_stake = &mut ctx.accounts.lp_stake;
+        settle_lp_rewards(lp_stake, ctx.accounts.lp_pool.acc_reward_per_share)?;
+        let reward = apply_lp_boost(lp_stake.pending_rewards, ctx.accounts.governance.lp_boost);
+        lp_stake.pending_rewards = 0;
+        lp_stake.last_claim_time = current_time;
+        emit_multi_yield(
+            &mut ctx.accounts.global_state,
+            &mut ctx.accounts.mint,
+            &mut ctx.accounts.reward_vault,
+            ctx.accounts.lp_reward_account.to_account_info(),
+            &ctx.accounts.token_program,
+            Emission::Lp,
+            reward,
+        )
+    }
+
+    /// Admin: create the LP emission controller, streaming `reward_per_second` multiYIELD
+    /// split across LP pools by allocation points.
+    pub fn initialize_lp_controller(ctx: Context<InitializeLpController>, reward_per_second: u64) -> Result<()> {
+        let lp_controller = &mut ctx.accounts.lp_controller;
+        lp_controller.reward_per_second = reward_per_second;
+        lp_controller.total_alloc_point = 0;
+        lp_controller.pool_count = 0;
+        Ok(())
+    }
+
+    /// Admin: register an LP pool and its PDA-owned vault for `lp_mint`. It starts with zero
+    /// allocation points until governance gives it a weight.
+    pub fn initialize_lp_pool(ctx: Context<InitializeLpPool>) -> Result<()> {
+        let lp_pool = &mut ctx.accounts.lp_pool;
+        lp_pool.lp_mint = ctx.accounts.lp_mint.key();
+        lp_pool.vault = ctx.accounts.lp_vault.key();
+        lp_pool.alloc_point = 0;
+        lp_pool.last_update = Clock::get()?.unix_timestamp;
+
+        let lp_controller = &mut ctx.accounts.lp_controller;
+        lp_controller.pool_count = lp_controller.pool_count.checked_add(1).ok_or(CustomError::ArithmeticOverflow)?;
+        Ok(())
+    }
+
+    /// Admin: configure how an LP pool's tokens are valued: the AMM's two reserve token accounts
+    /// and a Pyth USD feed for each leg.
+    pub fn set_lp_pool_pricing(
+        ctx: Context<SetLpPoolPricing>,
+        price_feed_a: Pubkey,
+        price_feed_b: Pubkey,
+    ) -> Result<()> {
+        let lp_pool = &mut ctx.accounts.lp_pool;
+        lp_pool.reserve_a = ctx.accounts.reserve_a.key();
+        lp_pool.reserve_b = ctx.accounts.reserve_b.key();
+        lp_pool.price_feed_a = price_feed_a;
+        lp_pool.price_feed_b = price_feed_b;
+        lp_pool.decimals_a = ctx.accounts.mint_a.decimals;
+        lp_pool.decimals_b = ctx.accounts.mint_b.decimals;
+        Ok(())
+    }
+
+    /// Admin: change the total LP emission rate. Pass every `LpPool` in `remaining_accounts`
+    /// so rewards accrued so far keep the old rate.
+    pub fn set_lp_reward_rate<'info>(
+        ctx: Context<'_, '_, 'info, 'info, SetLpRewardRate<'info>>,
+        reward_per_second: u64,
+    ) -> Result<()> {
+        let pools: Vec<Pubkey> = ctx.remaining_accounts.iter().map(|info| info.key()).collect();
+        require!(
+            is_every_lp_pool(&pools, ctx.accounts.lp_controller.pool_count),
+            CustomError::InvalidRemainingAccounts
+        );
+        let now = Clock::get()?.unix_timestamp;
+        update_lp_pools(ctx.remaining_accounts, None, &ctx.accounts.lp_controller, now)?;
+        ctx.accounts.lp_controller.reward_per_second = reward_per_second;
+        Ok(())
+    }
+
+    /// Governance: set an LP pool's allocation points, i.e. its share of LP emissions. Pass the
+    /// other `LpPool`s in `remaining_accounts` so their accrual up to now uses the old split.
+    pub fn set_lp_pool_alloc<'info>(
+        ctx: Context<'_, '_, 'info, 'info, SetLpPoolAlloc<'info>>,
+        alloc_point: u64,
+    ) -> Result<()> {
+        let now = Clock::get()?.unix_timestamp;
+        let action = governance_action_hash(
+            "set_lp_pool_alloc",
+            &(ctx.accounts.lp_pool.lp_mint, alloc_point).try_to_vec()?,
+        );
+        execute_proposal(&mut ctx.accounts.proposal, action, now)?;
+        let lp_pool_key = ctx.accounts.lp_pool.key();
+        let mut pools: Vec<Pubkey> = ctx.remaining_accounts.iter().map(|info| info.key()).collect();
+        pools.push(lp_pool_key);
+        require!(
+            is_every_lp_pool(&pools, ctx.accounts.lp_controller.pool_count),
+            CustomError::InvalidRemainingAccounts
+        );
+        update_lp_pools(ctx.remaining_accounts, Some(lp_pool_key), &ctx.accounts.lp_controller, now)?;
+        update_lp_pool(&mut ctx.accounts.lp_pool, &ctx.accounts.lp_controller, now);
+
+        let lp_controller = &mut ctx.accounts.lp_controller;
+        lp_controller.total_alloc_point = lp_controller
+            .total_alloc_point
+            .checked_sub(ctx.accounts.lp_pool.alloc_point)
+            .and_then(|total| total.checked_add(alloc_point))
+            .ok_or(CustomError::ArithmeticOverflow)?;
+        ctx.accounts.lp_pool.alloc_point = alloc_point;
+        Ok(())
+    }
+
+    /// Admin: start gauge voting with epochs of `epoch_duration` seconds. Governance fills in
+    /// the gauge list with `set_gauge_mints` before the first rollover.
+    pub fn initialize_gauge_controller(ctx: Context<InitializeGaugeController>, epoch_duration: i64) -> Result<()> {
+        require!(epoch_duration > 0, CustomError::InvalidLockDuration);
+        let gauge_controller = &mut ctx.accounts.gauge_controller;
+        gauge_controller.epoch = 0;
+        gauge_controller.epoch_start = Clock::get()?.unix_timestamp;
+        gauge_controller.epoch_duration = epoch_duration;
+        ctx.accounts.tally.epoch = 0;
+        Ok(())
+    }
+
+    /// Governance: set which LP mints can receive gauge votes. Takes effect at the next epoch.
+    /// Pass every `LpPool` in `remaining_accounts`: each mint must have one and none may repeat,
+    /// so the epoch rollover can always match the gauges to their pools.
+    pub fn set_gauge_mints<'info>(
+        ctx: Context<'_, '_, 'info, 'info, SetGaugeMints<'info>>,
+        mints: Vec<Pubkey>,
+    ) -> Result<()> {
+        let action = governance_action_hash("set_gauge_mints", &mints.try_to_vec()?);
+        execute_proposal(&mut ctx.accounts.proposal, action, Clock::get()?.unix_timestamp)?;
+        require!(mints.len() <= MAX_GAUGES, CustomError::TooManyGauges);
+
+        let pools: Vec<Pubkey> = ctx.remaining_accounts.iter().map(|info| info.key()).collect();
+        require!(
+            is_every_lp_pool(&pools, ctx.accounts.lp_controller.pool_count),
+            CustomError::InvalidRemainingAccounts
+        );
+        let pool_mints = ctx
+            .remaining_accounts
+            .iter()
+            .map(|info| Account::<LpPool>::try_from(info).map(|lp_pool| lp_pool.lp_mint))
+            .collect::<Result<Vec<Pubkey>>>()?;
+        require!(is_valid_gauge_list(&mints, &pool_mints), CustomError::InvalidGaugeMints);
+        ctx.accounts.gauge_controller.gauges = mints;
+        Ok(())
+    }
+
+    /// Split the voter's current veMY power across this epoch's gauges (`weights_bps` in gauge
+    /// order, summing to at most 100%). Voting again in the same epoch replaces the earlier vote.
+    pub fn vote_gauge_weights(ctx: Context<VoteGaugeWeights>, weights_bps: Vec<u16>) -> Result<()> {
+        let tally = &mut ctx.accounts.tally;
+        require!(weights_bps.len() == tally.gauges.len(), CustomError::InvalidGaugeVote);
+        require!(
+            weights_bps.iter().map(|&w| w as u64).sum::<u64>() <= 10_000,
+            CustomError::InvalidGaugeVote
+        );
+
+        let current_time = Clock::get()?.unix_timestamp;
+        let ve_lock = &ctx.accounts.ve_lock;
+        let power = ve_voting_power(ve_lock.amount, ve_lock.lock_end, current_time);
+        require!(power > 0, CustomError::LockExpired);
+
+        let gauge_vote = &mut ctx.accounts.gauge_vote;
+        if gauge_vote.epoch == tally.epoch {
+            for (i, &votes) in gauge_vote.votes.iter().enumerate() {
+                tally.votes[i] = tally.votes[i].saturating_sub(votes);
+                tally.total = tally.total.saturating_sub(votes);
+            }
+        }
+        gauge_vote.voter = ctx.accounts.voter.key();
+        gauge_vote.epoch = tally.epoch;
+        gauge_vote.votes = gauge_vote_split(power, &weights_bps);
+        for (i, &votes) in gauge_vote.votes.iter().enumerate() {
+            tally.votes[i] = tally.votes[i].saturating_add(votes);
+            tally.total = tally.total.saturating_add(votes);
+        }
+        Ok(())
+    }
+
+    /// Permissionless: close the gauge epoch once it has run its length, write the tallied votes
+    /// as the gauges' LP allocation points and open the next epoch's tally. `remaining_accounts`
+    /// holds the closing tally's `LpPool`s in gauge order, then every other `LpPool`, so each
+    /// pool appears exactly once. With no votes cast, allocations are left as they were.
+    pub fn roll_gauge_epoch<'info>(ctx: Context<'_, '_, 'info, 'info, RollGaugeEpoch<'info>>) -> Result<()> {
+        let now = Clock::get()?.unix_timestamp;
+        let gauge_controller = &mut ctx.accounts.gauge_controller;
+        require!(
+            now >= gauge_controller.epoch_start + gauge_controller.epoch_duration,
+            CustomError::EpochNotOver
+        );
+
+        let tally = &ctx.accounts.tally;
+        let pools: Vec<Pubkey> = ctx.remaining_accounts.iter().map(|info| info.key()).collect();
+        require!(
+            pools.len() >= tally.gauges.len() && is_every_lp_pool(&pools, ctx.accounts.lp_controller.pool_count),
+            CustomError::InvalidRemainingAccounts
+        );
+        let (gauge_pools, other_pools) = ctx.remaining_accounts.split_at(tally.gauges.len());
+        let lp_controller = &mut ctx.accounts.lp_controller;
+        update_lp_pools(other_pools, None, lp_controller, now)?;
+
+        if tally.total > 0 {
+            let mut total_alloc_point = lp_controller.total_alloc_point;
+            for (i, info) in gauge_pools.iter().enumerate() {
+                let mut lp_pool: Account<LpPool> = Account::try_from(info)?;
+                require!(lp_pool.lp_mint == tally.gauges[i], CustomError::InvalidRemainingAccounts);
+                update_lp_pool(&mut lp_pool, lp_controller, now);
+                total_alloc_point = total_alloc_point
+                    .checked_sub(lp_pool.alloc_point)
+                    .and_then(|total| total.checked_add(tally.votes[i]))
+                    .ok_or(CustomError::ArithmeticOverflow)?;
+                lp_pool.alloc_point = tally.votes[i];
+                lp_pool.exit(&crate::ID)?;
+            }
+            lp_controller.total_alloc_point = total_alloc_point;
+        }
+
+        gauge_controller.epoch += 1;
+        gauge_controller.epoch_start = now;
+        let next_tally = &mut ctx.accounts.next_tally;
+        next_tally.epoch = gauge_controller.epoch;
+        next_tally.gauges = gauge_controller.gauges.clone();
+        next_tally.votes = vec![0; next_tally.gauges.len()];
+        next_tally.total = 0;
+        Ok(())
+    }
+
+    /// Governance: update the base reward and LP boost, as authorized by a passed proposal.
+    pub fn update_reward_parameters(
+        ctx: Context<UpdateGovernance>,
+        new_reward: u8,
+        new_lp_boost: u8,
+    ) -> Result<()> {
+        require!(new_reward <= 50, CustomError::InvalidRewardParameters);
+        require!(new_lp_boost <= 10, CustomError::InvalidRewardParameters);
+
+        // On-chain DAO logic: the proposal must authorize exactly these parameters
+        let action = governance_action_hash("update_reward_parameters", &(new_reward, new_lp_boost).try_to_vec()?);
+        execute_proposal(&mut ctx.accounts.proposal, action, Clock::get()?.unix_timestamp)?;
+
+        let governance = &mut ctx.accounts.governance;
+        governance.reward_percentage = new_reward;
+        governance.lp_boost = new_lp_boost;
+        Ok(())
+    }
+
+    /// Governance: allowlist an NFT collection with its own floor feed, floor threshold and boost.
+    /// Boosts expire `boost_duration` seconds after staking or refresh.
+    pub fn set_collection_config(
+        ctx: Context<SetCollectionConfig>,
+        collection_mint: Pubkey,
+        floor_price_feed: Pubkey,
+        min_floor_price: i64,
+        boost_bps: u16,
+        boost_duration: i64,
+    ) -> Result<()> {
+        let action = governance_action_hash(
+            "set_collection_config",
+            &(collection_mint, floor_price_feed, min_floor_price, boost_bps, boost_duration).try_to_vec()?,
+        );
+        execute_proposal(&mut ctx.accounts.proposal, action, Clock::get()?.unix_timestamp)?;
+        require!(boost_bps <= 5_000, CustomError::InvalidRewardParameters);
+        require!(boost_duration > 0, CustomError::InvalidLockDuration);
+
+        let collection_config = &mut ctx.accounts.collection_config;
+        collection_config.collection_mint = collection_mint;
+        collection_config.floor_price_feed = floor_price_feed;
+        collection_config.min_floor_price = min_floor_price;
+        collection_config.boost_bps = boost_bps;
+        collection_config.boost_duration = boost_duration;
+        Ok(())
+    }
+
+    /// Governance: set a collection's rarity tier bonuses and how tiers are attested
+    /// (a co-signing tier authority and/or a merkle root of (mint, tier) leaves).
+    pub fn set_collection_tiers(
+        ctx: Context<UpdateCollectionConfig>,
+        tier_authority: Pubkey,
+        tier_merkle_root: [u8; 32],
+        tier_boost_bps: [u16; 4],
+    ) -> Result<()> {
+        let action = governance_action_hash(
+            "set_collection_tiers",
+            &(ctx.accounts.collection_config.collection_mint, tier_authority, tier_merkle_root, tier_boost_bps)
+                .try_to_vec()?,
+        );
+        execute_proposal(&mut ctx.accounts.proposal, action, Clock::get()?.unix_timestamp)?;
+        require!(
+            tier_boost_bps.iter().all(|bps| *bps <= 5_000),
+            CustomError::InvalidRewardParameters
+        );
+
+        let collection_config = &mut ctx.accounts.collection_config;
+        collection_config.tier_authority = tier_authority;
+        collection_config.tier_merkle_root = tier_merkle_root;
+        collection_config.tier_boost_bps = tier_boost_bps;
+        Ok(())
+    }
+
+    /// Governance: remove a collection from the allowlist. Already-staked NFTs keep their boost.
+    pub fn remove_collection_config(ctx: Context<RemoveCollectionConfig>) -> Result<()> {
+        let action = governance_action_hash(
+            "remove_collection_config",
+            &ctx.accounts.collection_config.collection_mint.try_to_vec()?,
+        );
+        execute_proposal(&mut ctx.accounts.proposal, action, Clock::get()?.unix_timestamp)
+    }
+}
+
+// -----------------------------------------------
+//                Accounts Contexts
+// -----------------------------------------------
+#[derive(Accounts)]
+pub struct Initialize<'info> {
+    #[account(
+        init,
+        payer = user,
+        seeds = [b"global_state"],
+        bump,
+        space = GLOBAL_STATE_SPACE
+    )]
+    pub global_state: Account<'info, GlobalState>,
+
+    #[account(
+        init,
+        payer = user,
+        // reward_percentage, lp_boost
+        space = 8 + 1 + 1,
+        seeds = [b"governance"],
+        bump
+    )]
+    pub governance: Account<'info, Governance>,
+
+    #[account(
+        init,
+        payer = user,
+        // acc_fee_per_share, vault_count
+        space = 8 + 16 * MAX_FEE_VAULTS + 1,
+        seeds = [b"fee_registry"],
+        bump
+    )]
+    pub fee_registry: Account<'info, FeeRegistry>,
+
+    #[account(
+        init,
+        payer = user,
+        mint::decimals = 6,
+        mint::authority = global_state,
+    )]
+    pub mint: InterfaceAccount<'info, Mint>,
+
+    #[account(mut)]
+    pub user: Signer<'info>,
+
+    pub system_program: Program<'info, System>,
+    pub token_program: Interface<'info, TokenInterface>,
+    pub rent: Sysvar<'info, Rent>,
+}
+
+#[derive(Accounts)]
+pub struct InitializeToken2022<'info> {
+    #[account(
+        init,
+        payer = user,
+        seeds = [b"global_state"],
+        bump,
+        space = GLOBAL_STATE_SPACE
+    )]
+    pub global_state: Account<'info, GlobalState>,
+
+    #[account(
+        init,
+        payer = user,
+        // reward_percentage, lp_boost
+        space = 8 + 1 + 1,
+        seeds = [b"governance"],
+        bump
+    )]
+    pub governance: Account<'info, Governance>,
+
+    #[account(
+        init,
+        payer = user,
+        // acc_fee_per_share, vault_count
+        space = 8 + 16 * MAX_FEE_VAULTS + 1,
+        seeds = [b"fee_registry"],
+        bump
+    )]
+    pub fee_registry: Account<'info, FeeRegistry>,
+
+    /// The new Token-2022 mint; allocated with its extensions in the handler.
+    #[account(mut)]
+    pub mint: Signer<'info>,
+
+    #[account(mut)]
+    pub user: Signer<'info>,
+
+    pub system_program: Program<'info, System>,
+    pub token_program: Program<'info, Token2022>,
+}
+
+#[derive(Accounts)]
+pub struct MigrateGlobalState<'info> {
+    /// CHECK: global_state in the original layout; resized and rewritten in the handler.
+    #[account(mut, seeds = [b"global_state"], bump, owner = crate::ID)]
+    pub global_state: UncheckedAccount<'info>,
+
+    #[account(
+        init,
+        payer = authority,
+        // reward_percentage, lp_boost
+        space = 8 + 1 + 1,
+        seeds = [b"governance"],
+        bump
+    )]
+    pub governance: Account<'info, Governance>,
+
+    #[account(
+        init,
+        payer = authority,
+        // acc_fee_per_share, vault_count
+        space = 8 + 16 * MAX_FEE_VAULTS + 1,
+        seeds = [b"fee_registry"],
+        bump
+    )]
+    pub fee_registry: Account<'info, FeeRegistry>,
+
+    #[account(constraint = program.programdata_address()? == Some(program_data.key()) @ CustomError::Unauthorized)]
+    pub program: Program<'info, crate::program::MultiYield>,
+
+    #[account(constraint = program_data.upgrade_authority_address == Some(authority.key()) @ CustomError::Unauthorized)]
+    pub program_data: Account<'info, ProgramData>,
+
+    #[account(mut)]
+    pub authority: Signer<'info>,
+
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+pub struct MigrateStakeAccount<'info> {
+    /// CHECK: stake account in the original layout; resized and rewritten in the handler.
+    #[account(mut, seeds = [b"stake", staker_authority.key().as_ref()], bump, owner = crate::ID)]
+    pub staker: UncheckedAccount<'info>,
+
+    #[account(mut, seeds = [b"global_state"], bump = global_state.bump)]
+    pub global_state: Account<'info, GlobalState>,
+
+    #[account(seeds = [b"fee_registry"], bump)]
+    pub fee_registry: Account<'info, FeeRegistry>,
+
+    #[account(
+        init,
+        payer = staker_authority,
+        // owner, holder, shares, reward_debt, pending
+        space = 8 + 32 + 32 + 8 + 16 * MAX_FEE_VAULTS + 8 * MAX_FEE_VAULTS,
+        seeds = [b"fee_claim", staker.key().as_ref()],
+        bump
+    )]
+    pub fee_claim: Account<'info, FeeClaim>,
+
+    #[account(mut)]
+    pub staker_authority: Signer<'info>,
+
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+pub struct MigrateNFTStake<'info> {
+    /// CHECK: NFT stake account from before NFT stacking; resized and rewritten in the handler.
+    #[account(mut, seeds = [b"nft_stake", user.key().as_ref()], bump, owner = crate::ID)]
+    pub nft_stake: UncheckedAccount<'info>,
+
+    /// Escrow of the NFT the single-NFT layout has staked, if any; checked in the handler.
+    pub nft_escrow: Option<InterfaceAccount<'info, TokenAccount>>,
+
+    #[account(mut)]
+    pub user: Signer<'info>,
+
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+pub struct HarvestTransferFees<'info> {
+    #[account(
+        seeds = [b"global_state"],
+        bump = global_state.bump,
+        has_one = mint,
+        has_one = fee_destination @ CustomError::InvalidFeeDestination
+    )]
+    pub global_state: Account<'info, GlobalState>,
+
+    #[account(mut)]
+    pub mint: InterfaceAccount<'info, Mint>,
+
+    #[account(mut, token::mint = mint)]
+    pub fee_destination: InterfaceAccount<'info, TokenAccount>,
+
+    pub token_program: Program<'info, Token2022>,
+}
+
+#[derive(Accounts)]
+pub struct UpdateGlobalConfigWithMint<'info> {
+    #[account(
+        mut,
+        seeds = [b"global_state"],
+        bump = global_state.bump,
+        has_one = authority @ CustomError::Unauthorized,
+        has_one = mint
+    )]
+    pub global_state: Account<'info, GlobalState>,
+    pub mint: InterfaceAccount<'info, Mint>,
+    pub authority: Signer<'info>,
+}
+
+#[derive(Accounts)]
+pub struct InitializeStakingPool<'info> {
+    #[account(
+        seeds = [b"global_state"],
+        bump = global_state.bump,
+        has_one = authority @ CustomError::Unauthorized,
+        has_one = mint
+    )]
+    pub global_state: Account<'info, GlobalState>,
+
+    #[account(
+        init,
+        payer = authority,
+        seeds = [b"staking_pool"],
+        bump,
+        token::mint = mint,
+        token::authority = global_state,
+    )]
+    pub staking_pool_token_account: InterfaceAccount<'info, TokenAccount>,
+
+    pub mint: InterfaceAccount<'info, Mint>,
+
+    #[account(mut)]
+    pub authority: Signer<'info>,
+
+    pub system_program: Program<'info, System>,
+    pub token_program: Interface<'info, TokenInterface>,
+    pub rent: Sysvar<'info, Rent>,
+}
+
+#[derive(Accounts)]
+pub struct InitializeRewardVault<'info> {
+    #[account(
+        mut,
+        seeds = [b"global_state"],
+        bump = global_state.bump,
+        has_one = authority @ CustomError::Unauthorized,
+        has_one = mint
+    )]
+    pub global_state: Account<'info, GlobalState>,
+
+    #[account(
+        init,
+        payer = authority,
+        seeds = [b"reward_vault"],
+        bump,
+        token::mint = mint,
+        token::authority = global_state,
+    )]
+    pub reward_vault: InterfaceAccount<'info, TokenAccount>,
+
+    pub mint: InterfaceAccount<'info, Mint>,
+
+    #[account(mut)]
+    pub authority: Signer<'info>,
+
+    pub system_program: Program<'info, System>,
+    pub token_program: Interface<'info, TokenInterface>,
+    pub rent: Sysvar<'info, Rent>,
+}
+
+#[derive(Accounts)]
+pub struct FundRewardVault<'info> {
+    #[account(seeds = [b"global_state"], bump = global_state.bump, has_one = mint)]
+    pub global_state: Account<'info, GlobalState>,
+
+    #[account(mut, address = global_state.reward_vault @ CustomError::InvalidRewardVault)]
+    pub reward_vault: InterfaceAccount<'info, TokenAccount>,
+
+    pub mint: InterfaceAccount<'info, Mint>,
+
+    #[account(mut)]
+    pub funder_token_account: InterfaceAccount<'info, TokenAccount>,
+
+    pub funder: Signer<'info>,
+
+    pub token_program: Interface<'info, TokenInterface>,
+}
+
+#[derive(Accounts)]
+pub struct RewardVaultRunway<'info> {
+    #[account(seeds = [b"global_state"], bump = global_state.bump)]
+    pub global_state: Account<'info, GlobalState>,
+
+    #[account(address = global_state.reward_vault @ CustomError::InvalidRewardVault)]
+    pub reward_vault: InterfaceAccount<'info, TokenAccount>,
+}
+
+#[derive(Accounts)]
+pub struct CreateBuybackVault<'info> {
+    #[account(
+        mut,
+        seeds = [b"global_state"],
+        bump = global_state.bump,
+        has_one = authority @ CustomError::Unauthorized,
+        has_one = mint
+    )]
+    pub global_state: Account<'info, GlobalState>,
+
+    #[account(
+        init,
+        payer = authority,
+        seeds = [b"buyback_vault", quote_mint.key().as_ref()],
+        bump,
+        token::mint = quote_mint,
+        token::authority = buyback_authority,
+    )]
+    pub buyback_vault: InterfaceAccount<'info, TokenAccount>,
+
+    /// Shared by every quote mint's buyback; created with the first vault.
+    #[account(
+        init_if_needed,
+        payer = authority,
+        seeds = [b"buyback_burn"],
+        bump,
+        token::mint = mint,
+        token::authority = buyback_authority,
+    )]
+    pub burn_account: InterfaceAccount<'info, TokenAccount>,
+
+    /// CHECK: data-less PDA that owns the buyback accounts and signs their swaps and burns.
+    #[account(seeds = [b"buyback_authority"], bump)]
+    pub buyback_authority: UncheckedAccount<'info>,
+
+    pub quote_mint: InterfaceAccount<'info, Mint>,
+    pub mint: InterfaceAccount<'info, Mint>,
+
+    #[account(mut)]
+    pub authority: Signer<'info>,
+
+    pub system_program: Program<'info, System>,
+    pub token_program: Interface<'info, TokenInterface>,
+    pub rent: Sysvar<'info, Rent>,
+}
+
+#[derive(Accounts)]
+pub struct BuybackAndBurn<'info> {
+    #[account(
+        mut,
+        seeds = [b"global_state"],
+        bump = global_state.bump,
+        has_one = authority @ CustomError::Unauthorized,
+        has_one = mint
+    )]
+    pub global_state: Account<'info, GlobalState>,
+
+    #[account(mut)]
+    pub mint: InterfaceAccount<'info, Mint>,
+
+    pub quote_mint: InterfaceAccount<'info, Mint>,
+
+    #[account(mut, seeds = [b"buyback_vault", quote_mint.key().as_ref()], bump)]
+    pub buyback_vault: InterfaceAccount<'info, TokenAccount>,
+
+    #[account(mut, seeds = [b"buyback_burn"], bump)]
+    pub burn_account: InterfaceAccount<'info, TokenAccount>,
+
+    /// CHECK: data-less PDA that owns the buyback accounts and signs their swaps and burns.
+    #[account(seeds = [b"buyback_authority"], bump = global_state.buyback_authority_bump)]
+    pub buyback_authority: UncheckedAccount<'info>,
+
+    /// CHECK: pinned to the configured Pyth feed and parsed in the handler.
+    #[account(address = global_state.buyback_price_feed @ CustomError::OracleError)]
+    pub pyth_price_feed: UncheckedAccount<'info>,
+
+    /// CHECK: only the whitelisted AMM can be invoked.
+    #[account(executable, address = global_state.buyback_amm_program @ CustomError::AmmNotWhitelisted)]
+    pub amm_program: UncheckedAccount<'info>,
+
+    pub authority: Signer<'info>,
+
+    pub token_program: Interface<'info, TokenInterface>,
+}
+
+#[derive(Accounts)]
+pub struct CreateTokenMetadata<'info> {
+    #[account(
+        seeds = [b"global_state"],
+        bump = global_state.bump,
+        has_one = authority @ CustomError::Unauthorized,
+        has_one = mint
+    )]
+    pub global_state: Account<'info, GlobalState>,
+
+    #[account(mut)]
+    pub mint: InterfaceAccount<'info, Mint>,
+
+    /// CHECK: Metaplex metadata PDA for the mint, created by the CPI.
+    #[account(
+        mut,
+        seeds = [b"metadata", TOKEN_METADATA_PROGRAM_ID.as_ref(), mint.key().as_ref()],
+        seeds::program = TOKEN_METADATA_PROGRAM_ID,
+        bump
+    )]
+    pub metadata: UncheckedAccount<'info>,
+
+    #[account(mut)]
+    pub authority: Signer<'info>,
+
+    pub token_metadata_program: Program<'info, Metadata>,
+    pub system_program: Program<'info, System>,
+    pub rent: Sysvar<'info, Rent>,
+}
+
+#[derive(Accounts)]
+pub struct UpdateTokenMetadata<'info> {
+    #[account(
+        seeds = [b"global_state"],
+        bump = global_state.bump,
+        has_one = authority @ CustomError::Unauthorized,
+        has_one = mint
+    )]
+    pub global_state: Account<'info, GlobalState>,
+
+    pub mint: InterfaceAccount<'info, Mint>,
+
+    #[account(
+        mut,
+        seeds = [b"metadata", TOKEN_METADATA_PROGRAM_ID.as_ref(), mint.key().as_ref()],
+        seeds::program = TOKEN_METADATA_PROGRAM_ID,
+        bump
+    )]
+    pub metadata: Account<'info, MetadataAccount>,
+
+    pub authority: Signer<'info>,
+
+    pub token_metadata_program: Program<'info, Metadata>,
+}
+
+#[derive(Accounts)]
+pub struct UpdateGlobalConfig<'info> {
+    #[account(
+        mut,
+        seeds = [b"global_state"],
+        bump = global_state.bump,
+        has_one = authority @ CustomError::Unauthorized
+    )]
+    pub global_state: Account<'info, GlobalState>,
+    pub authority: Signer<'info>,
+}
+
+#[derive(Accounts)]
+pub struct RewardTrade<'info> {
+    #[account(mut, seeds = [b"global_state"], bump = global_state.bump)]
+    pub global_state: Account<'info, GlobalState>,
+
+    // for dynamic protocol volume adjustments
+    // (publicly stored in global_state -> protocol_wide_volume)
+    // which can increment on each trade if desired.
+
+
+
+    #[account(mut)]
+    pub mint: InterfaceAccount<'info, Mint>,
+
+    #[account(mut)]
+    pub trader_token_account: InterfaceAccount<'info, TokenAccount>,
+
+    #[account(mut)]
+    pub insurance_pool_account: InterfaceAccount<'info, TokenAccount>,
+
+    #[account(
+        mut,
+        seeds = [b"volume", trader_token_account.owner.key().as_ref()],
+        bump
+    )]
+    pub trader_volume: Account<'info, TraderVolume>,
+
+    /// Pyth price feed as a generic AccountInfo
+    #[account()]
+    pub pyth_price_feed: AccountInfo<'info>,
+
+    /// Required once rewards are paid from the reward vault instead of minted.
+    #[account(mut, address = global_state.reward_vault @ CustomError::InvalidRewardVault)]
+    pub reward_vault: Option<InterfaceAccount<'info, TokenAccount>>,
+
+    pub token_program: Interface<'info, TokenInterface>,
+}
+
+#[derive(Accounts)]
+pub struct BondTokens<'info> {
+    #[account(
+        init_if_needed,
+        payer = staker_authority,
+        space = STAKE_ACCOUNT_SPACE,
+        seeds = [b"stake", staker_authority.key().as_ref()],
+        bump
+    )]
+    pub staker: Account<'info, StakeAccount>,
+
+    #[account(mut)]
+    pub staker_token_account: InterfaceAccount<'info, TokenAccount>,
+
+    #[account(mut, seeds = [b"staking_pool"], bump)]
+    pub staking_pool_token_account: InterfaceAccount<'info, TokenAccount>,
+
+    #[account(mut, seeds = [b"global_state"], bump = global_state.bump)]
+    pub global_state: Account<'info, GlobalState>,
+
+    #[account(mut)]
+    pub staker_authority: Signer<'info>,
+
+    #[account(seeds = [b"fee_registry"], bump)]
+    pub fee_registry: Account<'info, FeeRegistry>,
+
+    #[account(
+        init_if_needed,
+        payer = staker_authority,
+        // owner, holder, shares, reward_debt, pending
+        space = 8 + 32 + 32 + 8 + 16 * MAX_FEE_VAULTS + 8 * MAX_FEE_VAULTS,
+        seeds = [b"fee_claim", staker.key().as_ref()],
+        bump
+    )]
+    pub fee_claim: Account<'info, FeeClaim>,
+
+    #[account(address = global_state.mint)]
+    pub mint: InterfaceAccount<'info, Mint>,
+
+    pub token_program: Interface<'info, TokenInterface>,
+    pub system_program: Program<'info, System>,
+    pub rent: Sysvar<'info, Rent>,
+}
+
+#[derive(Accounts)]
+pub struct SetDelegate<'info> {
+    #[account(mut, seeds = [b"stake", staker_authority.key().as_ref()], bump)]
+    pub staker: Account<'info, StakeAccount>,
+    pub staker_authority: Signer<'info>,
+}
+
+#[derive(Accounts)]
+pub struct ClaimStakeRewards<'info> {
+    #[account(
+        mut,
+        seeds = [b"stake", staker.owner.as_ref()],
+        bump,
+        constraint = staker.can_claim(&authority.key()) @ CustomError::Unauthorized
+    )]
+    pub staker: Account<'info, StakeAccount>,
+
+    #[account(mut, token::authority = staker.owner)]
+    pub staker_reward_account: InterfaceAccount<'info, TokenAccount>,
+
+    #[account(
+        mut,
+        seeds = [b"global_state"],
+        bump = global_state.bump
+    )]
+    pub global_state: Account<'info, GlobalState>,
+
+    #[account(seeds = [b"governance"], bump)]
+    pub governance: Account<'info, Governance>,
+
+    #[account(mut)]
+    pub mint: InterfaceAccount<'info, Mint>,
+
+    /// The staked NFT account for reward boosts (if any).
+    #[account(seeds = [b"nft_stake", staker.owner.as_ref()], bump)]
+    pub nft_stake: Option<Account<'info, NFTStakeAccount>>,
+
+    /// The staker's veMY lock, for the continuous loyalty boost.
+    #[account(seeds = [b"ve_lock", staker.owner.as_ref()], bump)]
+    pub ve_lock: Option<Account<'info, VeLock>>,
+
+    /// The DAO treasury (for penalty fees).
+    #[account(mut, address = global_state.dao_treasury @ CustomError::InvalidTreasury)]
+    pub dao_treasury_account: InterfaceAccount<'info, TokenAccount>,
+
+    /// The stake owner or their delegate.
+    pub authority: Signer<'info>,
+    /// Required once rewards are paid from the reward vault instead of minted.
+    #[account(mut, address = global_state.reward_vault @ CustomError::InvalidRewardVault)]
+    pub reward_vault: Option<InterfaceAccount<'info, TokenAccount>>,
+
+    pub token_program: Interface<'info, TokenInterface>,
+}
+
+#[derive(Accounts)]
+pub struct CrankCompound<'info> {
+    #[account(mut, seeds = [b"global_state"], bump = global_state.bump)]
+    pub global_state: Account<'info, GlobalState>,
+
+    #[account(seeds = [b"governance"], bump)]
+    pub governance: Account<'info, Governance>,
+
+    #[account(seeds = [b"fee_registry"], bump)]
+    pub fee_registry: Account<'info, FeeRegistry>,
+
+    #[account(mut, address = global_state.mint)]
+    pub mint: InterfaceAccount<'info, Mint>,
+
+    #[account(mut, seeds = [b"staking_pool"], bump)]
+    pub staking_pool_token_account: InterfaceAccount<'info, TokenAccount>,
+
+    /// The DAO treasury (for penalty fees).
+    #[account(mut, address = global_state.dao_treasury @ CustomError::InvalidTreasury)]
+    pub dao_treasury_account: InterfaceAccount<'info, TokenAccount>,
+
+    /// Receives the crank tip.
+    #[account(mut)]
+    pub cranker_token_account: InterfaceAccount<'info, TokenAccount>,
+
+    pub cranker: Signer<'info>,
+    /// Required once rewards are paid from the reward vault instead of minted.
+    #[account(mut, address = global_state.reward_vault @ CustomError::InvalidRewardVault)]
+    pub reward_vault: Option<InterfaceAccount<'info, TokenAccount>>,
+
+    pub token_program: Interface<'info, TokenInterface>,
+}
+
+#[derive(Accounts)]
+#[instruction(ticket_id: u64)]
+pub struct RequestUnstake<'info> {
+    #[account(mut, seeds = [b"stake", staker_authority.key().as_ref()], bump)]
+    pub staker: Account<'info, StakeAccount>,
+
+    #[account(
+        init,
+        payer = staker_authority,
+        space = 8 + 32 + 8 + 8 + 8 + 8,
+        seeds = [b"unstake_ticket", staker_authority.key().as_ref(), &ticket_id.to_le_bytes()],
+        bump
+    )]
+    pub unstake_ticket: Account<'info, UnstakeTicket>,
+
+    #[account(mut, seeds = [b"global_state"], bump = global_state.bump)]
+    pub global_state: Account<'info, GlobalState>,
+
+    #[account(seeds = [b"fee_registry"], bump)]
+    pub fee_registry: Account<'info, FeeRegistry>,
+
+    #[account(mut, seeds = [b"fee_claim", staker.key().as_ref()], bump)]
+    pub fee_claim: Account<'info, FeeClaim>,
+
+    #[account(mut)]
+    pub staker_authority: Signer<'info>,
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+pub struct ClaimUnstake<'info> {
+    #[account(
+        mut,
+        close = staker_authority,
+        seeds = [b"unstake_ticket", staker_authority.key().as_ref(), &unstake_ticket.ticket_id.to_le_bytes()],
+        bump
+    )]
+    pub unstake_ticket: Account<'info, UnstakeTicket>,
+
+    #[account(mut)]
+    pub staker_token_account: InterfaceAccount<'info, TokenAccount>,
+
+    #[account(mut, seeds = [b"staking_pool"], bump)]
+    pub staking_pool_token_account: InterfaceAccount<'info, TokenAccount>,
+
+    #[account(mut, seeds = [b"global_state"], bump = global_state.bump)]
+    pub global_state: Account<'info, GlobalState>,
+
+    #[account(mut)]
+    pub staker_authority: Signer<'info>,
+
+    #[account(address = global_state.mint)]
+    pub mint: InterfaceAccount<'info, Mint>,
+
+    pub token_program: Interface<'info, TokenInterface>,
+}
+
+#[derive(Accounts)]
+pub struct CancelUnstake<'info> {
+    #[account(mut, seeds = [b"stake", staker_authority.key().as_ref()], bump)]
+    pub staker: Account<'info, StakeAccount>,
+
+    #[account(
+        mut,
+        close = staker_authority,
+        seeds = [b"unstake_ticket", staker_authority.key().as_ref(), &unstake_ticket.ticket_id.to_le_bytes()],
+        bump
+    )]
+    pub unstake_ticket: Account<'info, UnstakeTicket>,
+
+    #[account(mut, seeds = [b"global_state"], bump = global_state.bump)]
+    pub global_state: Account<'info, GlobalState>,
+
+    #[account(seeds = [b"fee_registry"], bump)]
+    pub fee_registry: Account<'info, FeeRegistry>,
+
+    #[account(mut, seeds = [b"fee_claim", staker.key().as_ref()], bump)]
+    pub fee_claim: Account<'info, FeeClaim>,
+
+    #[account(mut)]
+    pub staker_authority: Signer<'info>,
+}
+
+#[derive(Accounts)]
+pub struct CreateFeeVault<'info> {
+    #[account(
+        seeds = [b"global_state"],
+        bump = global_state.bump,
+        has_one = authority @ CustomError::Unauthorized
+    )]
+    pub global_state: Account<'info, GlobalState>,
+
+    #[account(
+        init,
+        payer = authority,
+        space = 8 + 32 + 32 + 1 + 8,
+        seeds = [b"fee_vault", quote_mint.key().as_ref()],
+        bump
+    )]
+    pub fee_vault: Account<'info, FeeVault>,
+
+    #[account(mut, seeds = [b"fee_registry"], bump)]
+    pub fee_registry: Account<'info, FeeRegistry>,
+
+    #[account(
+        init,
+        payer = authority,
+        seeds = [b"fee_vault_tokens", quote_mint.key().as_ref()],
+        bump,
+        token::mint = quote_mint,
+        token::authority = global_state,
+    )]
+    pub fee_vault_token_account: InterfaceAccount<'info, TokenAccount>,
+
+    pub quote_mint: InterfaceAccount<'info, Mint>,
+
+    #[account(mut)]
+    pub authority: Signer<'info>,
+
+    pub system_program: Program<'info, System>,
+    pub token_program: Interface<'info, TokenInterface>,
+    pub rent: Sysvar<'info, Rent>,
+}
+
+#[derive(Accounts)]
+pub struct DepositProtocolFees<'info> {
+    #[account(mut, seeds = [b"fee_vault", fee_vault.quote_mint.as_ref()], bump)]
+    pub fee_vault: Account<'info, FeeVault>,
+
+    #[account(mut, seeds = [b"fee_registry"], bump)]
+    pub fee_registry: Account<'info, FeeRegistry>,
+
+    #[account(mut, address = fee_vault.vault)]
+    pub fee_vault_token_account: InterfaceAccount<'info, TokenAccount>,
+
+    #[account(mut, seeds = [b"global_state"], bump = global_state.bump)]
+    pub global_state: Account<'info, GlobalState>,
+
+    #[account(mut)]
+    pub depositor_token_account: InterfaceAccount<'info, TokenAccount>,
+
+    pub depositor: Signer<'info>,
+
+    #[account(address = fee_vault.quote_mint)]
+    pub quote_mint: InterfaceAccount<'info, Mint>,
+
+    pub token_program: Interface<'info, TokenInterface>,
+}
+
+#[derive(Accounts)]
+pub struct ClaimFeeRewards<'info> {
+    #[account(seeds = [b"fee_registry"], bump)]
+    pub fee_registry: Account<'info, FeeRegistry>,
+
+    #[account(seeds = [b"fee_vault", fee_vault.quote_mint.as_ref()], bump)]
+    pub fee_vault: Account<'info, FeeVault>,
+
+    #[account(mut, address = fee_vault.vault)]
+    pub fee_vault_token_account: InterfaceAccount<'info, TokenAccount>,
+
+    /// Fee claim of one of the caller's bonded stake, stake positions or veMY lock.
+    #[account(
+        mut,
+        seeds = [b"fee_claim", fee_claim.holder.as_ref()],
+        bump,
+        constraint = fee_claim.owner == staker_authority.key() @ CustomError::Unauthorized
+    )]
+    pub fee_claim: Account<'info, FeeClaim>,
+
+    #[account(mut, token::mint = fee_vault.quote_mint)]
+    pub staker_quote_account: InterfaceAccount<'info, TokenAccount>,
+
+    #[account(mut, seeds = [b"global_state"], bump = global_state.bump)]
+    pub global_state: Account<'info, GlobalState>,
+
+    #[account(mut)]
+    pub staker_authority: Signer<'info>,
+
+    #[account(address = fee_vault.quote_mint)]
+    pub quote_mint: InterfaceAccount<'info, Mint>,
+
+    pub token_program: Interface<'info, TokenInterface>,
+}
+
+#[derive(Accounts)]
+#[instruction(position_id: u64)]
+pub struct StakeToPosition<'info> {
+    #[account(
+        init_if_needed,
+        payer = staker_authority,
+        space = 8 + 32 + 8 + 8 + 8 + 8 + 1 + 32 + 8,
+        seeds = [b"stake_position", staker_authority.key().as_ref(), &position_id.to_le_bytes()],
+        bump
+    )]
+    pub position: Account<'info, StakePosition>,
+
+    #[account(mut)]
+    pub staker_token_account: InterfaceAccount<'info, TokenAccount>,
+
+    #[account(mut, seeds = [b"staking_pool"], bump)]
+    pub staking_pool_token_account: InterfaceAccount<'info, TokenAccount>,
+
+    #[account(mut, seeds = [b"global_state"], bump = global_state.bump)]
+    pub global_state: Account<'info, GlobalState>,
+
+    /// Optional NFT stake account whose boost this position should use.
+    #[account(seeds = [b"nft_stake", staker_authority.key().as_ref()], bump)]
+    pub nft_stake: Option<Account<'info, NFTStakeAccount>>,
+
+    #[account(mut)]
+    pub staker_authority: Signer<'info>,
+
+    #[account(seeds = [b"fee_registry"], bump)]
+    pub fee_registry: Account<'info, FeeRegistry>,
+
+    #[account(
+        init_if_needed,
+        payer = staker_authority,
+        // owner, holder, shares, reward_debt, pending
+        space = 8 + 32 + 32 + 8 + 16 * MAX_FEE_VAULTS + 8 * MAX_FEE_VAULTS,
+        seeds = [b"fee_claim", position.key().as_ref()],
+        bump
+    )]
+    pub fee_claim: Account<'info, FeeClaim>,
+
+    #[account(address = global_state.mint)]
+    pub mint: InterfaceAccount<'info, Mint>,
+
+    pub token_program: Interface<'info, TokenInterface>,
+    pub system_program: Program<'info, System>,
+    pub rent: Sysvar<'info, Rent>,
+}
+
+#[derive(Accounts)]
+pub struct ClaimPositionRewards<'info> {
+    #[account(
+        mut,
+        seeds = [b"stake_position", staker_authority.key().as_ref(), &position.position_id.to_le_bytes()],
+        bump
+    )]
+    pub position: Account<'info, StakePosition>,
+
+    #[account(mut)]
+    pub staker_reward_account: InterfaceAccount<'info, TokenAccount>,
+
+    /// Receives auto-compounded rewards.
+    #[account(mut, seeds = [b"staking_pool"], bump)]
+    pub staking_pool_token_account: InterfaceAccount<'info, TokenAccount>,
+
+    #[account(
+        mut,
+        seeds = [b"global_state"],
+        bump = global_state.bump
+    )]
+    pub global_state: Account<'info, GlobalState>,
+
+    #[account(seeds = [b"governance"], bump)]
+    pub governance: Account<'info, Governance>,
+
+    #[account(mut)]
+    pub mint: InterfaceAccount<'info, Mint>,
+
+    /// The NFT stake account linked to this position (if any).
+    pub nft_stake: Option<Account<'info, NFTStakeAccount>>,
+
+    #[account(seeds = [b"fee_registry"], bump)]
+    pub fee_registry: Account<'info, FeeRegistry>,
+
+    #[account(mut, seeds = [b"fee_claim", position.key().as_ref()], bump)]
+    pub fee_claim: Account<'info, FeeClaim>,
+
+    /// The DAO treasury (for penalty fees).
+    #[account(mut, address = global_state.dao_treasury @ CustomError::InvalidTreasury)]
+    pub dao_treasury_account: InterfaceAccount<'info, TokenAccount>,
+
+    pub staker_authority: Signer<'info>,
+    /// Required once rewards are paid from the reward vault instead of minted.
+    #[account(mut, address = global_state.reward_vault @ CustomError::InvalidRewardVault)]
+    pub reward_vault: Option<InterfaceAccount<'info, TokenAccount>>,
+
+    pub token_program: Interface<'info, TokenInterface>,
+}
+
+#[derive(Accounts)]
+pub struct MergeStakePositions<'info> {
+    #[account(
+        mut,
+        close = staker_authority,
+        seeds = [b"stake_position", staker_authority.key().as_ref(), &source.position_id.to_le_bytes()],
+        bump
+    )]
+    pub source: Account<'info, StakePosition>,
+
+    #[account(
+        mut,
+        seeds = [b"stake_position", staker_authority.key().as_ref(), &destination.position_id.to_le_bytes()],
+        bump
+    )]
+    pub destination: Account<'info, StakePosition>,
+
+    #[account(mut, seeds = [b"global_state"], bump = global_state.bump)]
+    pub global_state: Account<'info, GlobalState>,
+
+    #[account(seeds = [b"fee_registry"], bump)]
+    pub fee_registry: Account<'info, FeeRegistry>,
+
+    #[account(mut, close = staker_authority, seeds = [b"fee_claim", source.key().as_ref()], bump)]
+    pub source_fee_claim: Account<'info, FeeClaim>,
+
+    #[account(mut, seeds = [b"fee_claim", destination.key().as_ref()], bump)]
+    pub destination_fee_claim: Account<'info, FeeClaim>,
+
+    #[account(mut)]
+    pub staker_authority: Signer<'info>,
+}
+
+#[derive(Accounts)]
+#[instruction(new_position_id: u64)]
+pub struct SplitStakePosition<'info> {
+    #[account(
+        mut,
+        seeds = [b"stake_position", staker_authority.key().as_ref(), &source.position_id.to_le_bytes()],
+        bump
+    )]
+    pub source: Account<'info, StakePosition>,
+
+    #[account(
+        init,
+        payer = staker_authority,
+        space = 8 + 32 + 8 + 8 + 8 + 8 + 1 + 32 + 8,
+        seeds = [b"stake_position", staker_authority.key().as_ref(), &new_position_id.to_le_bytes()],
+        bump
+    )]
+    pub new_position: Account<'info, StakePosition>,
+
+    #[account(mut, seeds = [b"global_state"], bump = global_state.bump)]
+    pub global_state: Account<'info, GlobalState>,
+
+    #[account(seeds = [b"fee_registry"], bump)]
+    pub fee_registry: Account<'info, FeeRegistry>,
+
+    #[account(mut, seeds = [b"fee_claim", source.key().as_ref()], bump)]
+    pub source_fee_claim: Account<'info, FeeClaim>,
+
+    #[account(
+        init,
+        payer = staker_authority,
+        // owner, holder, shares, reward_debt, pending
+        space = 8 + 32 + 32 + 8 + 16 * MAX_FEE_VAULTS + 8 * MAX_FEE_VAULTS,
+        seeds = [b"fee_claim", new_position.key().as_ref()],
+        bump
+    )]
+    pub new_fee_claim: Account<'info, FeeClaim>,
+
+    #[account(mut)]
+    pub staker_authority: Signer<'info>,
+
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+pub struct WithdrawStakePosition<'info> {
+    #[account(
+        mut,
+        seeds = [b"stake_position", staker_authority.key().as_ref(), &position.position_id.to_le_bytes()],
+        bump
+    )]
+    pub position: Account<'info, StakePosition>,
+
+    #[account(mut)]
+    pub staker_token_account: InterfaceAccount<'info, TokenAccount>,
+
+    #[account(mut, seeds = [b"staking_pool"], bump)]
+    pub staking_pool_token_account: InterfaceAccount<'info, TokenAccount>,
+
+    #[account(mut, seeds = [b"global_state"], bump = global_state.bump)]
+    pub global_state: Account<'info, GlobalState>,
+
+    #[account(seeds = [b"fee_registry"], bump)]
+    pub fee_registry: Account<'info, FeeRegistry>,
+
+    #[account(mut, seeds = [b"fee_claim", position.key().as_ref()], bump)]
+    pub fee_claim: Account<'info, FeeClaim>,
+
+    pub staker_authority: Signer<'info>,
+
+    #[account(address = global_state.mint)]
+    pub mint: InterfaceAccount<'info, Mint>,
+
+    pub token_program: Interface<'info, TokenInterface>,
+}
+
+#[derive(Accounts)]
+pub struct CreateVeLock<'info> {
+    #[account(
+        init,
+        payer = staker_authority,
+        space = 8 + 32 + 8 + 8 + 8,
+        seeds = [b"ve_lock", staker_authority.key().as_ref()],
+        bump
+    )]
+    pub ve_lock: Account<'info, VeLock>,
+
+    #[account(mut)]
+    pub staker_token_account: InterfaceAccount<'info, TokenAccount>,
+
+    #[account(mut, seeds = [b"staking_pool"], bump)]
+    pub staking_pool_token_account: InterfaceAccount<'info, TokenAccount>,
+
+    #[account(mut, seeds = [b"global_state"], bump = global_state.bump)]
+    pub global_state: Account<'info, GlobalState>,
+
+    #[account(mut)]
+    pub staker_authority: Signer<'info>,
+
+    #[account(seeds = [b"fee_registry"], bump)]
+    pub fee_registry: Account<'info, FeeRegistry>,
+
+    #[account(
+        init_if_needed,
+        payer = staker_authority,
+        // owner, holder, shares, reward_debt, pending
+        space = 8 + 32 + 32 + 8 + 16 * MAX_FEE_VAULTS + 8 * MAX_FEE_VAULTS,
+        seeds = [b"fee_claim", ve_lock.key().as_ref()],
+        bump
+    )]
+    pub fee_claim: Account<'info, FeeClaim>,
+
+    #[account(address = global_state.mint)]
+    pub mint: InterfaceAccount<'info, Mint>,
+
+    pub token_program: Interface<'info, TokenInterface>,
+    pub system_program: Program<'info, System>,
+    pub rent: Sysvar<'info, Rent>,
+}
+
+#[derive(Accounts)]
+pub struct UpdateVeLock<'info> {
+    #[account(mut, seeds = [b"ve_lock", staker_authority.key().as_ref()], bump)]
+    pub ve_lock: Account<'info, VeLock>,
+
+    #[account(mut)]
+    pub staker_token_account: InterfaceAccount<'info, TokenAccount>,
+
+    #[account(mut, seeds = [b"staking_pool"], bump)]
+    pub staking_pool_token_account: InterfaceAccount<'info, TokenAccount>,
+
+    #[account(mut, seeds = [b"global_state"], bump = global_state.bump)]
+    pub global_state: Account<'info, GlobalState>,
+
+    pub staker_authority: Signer<'info>,
+
+    #[account(seeds = [b"fee_registry"], bump)]
+    pub fee_registry: Account<'info, FeeRegistry>,
+
+    #[account(mut, seeds = [b"fee_claim", ve_lock.key().as_ref()], bump)]
+    pub fee_claim: Account<'info, FeeClaim>,
+
+    #[account(address = global_state.mint)]
+    pub mint: InterfaceAccount<'info, Mint>,
+
+    pub token_program: Interface<'info, TokenInterface>,
+}
+
+#[derive(Accounts)]
+pub struct ExtendVeLock<'info> {
+    #[account(mut, seeds = [b"ve_lock", staker_authority.key().as_ref()], bump)]
+    pub ve_lock: Account<'info, VeLock>,
+
+    pub staker_authority: Signer<'info>,
+}
+
+#[derive(Accounts)]
+pub struct WithdrawVeLock<'info> {
+    #[account(
+        mut,
+        close = staker_authority,
+        seeds = [b"ve_lock", staker_authority.key().as_ref()],
+        bump
+    )]
+    pub ve_lock: Account<'info, VeLock>,
+
+    #[account(mut)]
+    pub staker_token_account: InterfaceAccount<'info, TokenAccount>,
+
+    #[account(mut, seeds = [b"staking_pool"], bump)]
+    pub staking_pool_token_account: InterfaceAccount<'info, TokenAccount>,
+
+    #[account(mut, seeds = [b"global_state"], bump = global_state.bump)]
+    pub global_state: Account<'info, GlobalState>,
+
+    #[account(mut)]
+    pub staker_authority: Signer<'info>,
+
+    #[account(seeds = [b"fee_registry"], bump)]
+    pub fee_registry: Account<'info, FeeRegistry>,
+
+    #[account(mut, seeds = [b"fee_claim", ve_lock.key().as_ref()], bump)]
+    pub fee_claim: Account<'info, FeeClaim>,
+
+    #[account(address = global_state.mint)]
+    pub mint: InterfaceAccount<'info, Mint>,
+
+    pub token_program: Interface<'info, TokenInterface>,
+}
+
+#[derive(Accounts)]
+#[instruction(proposal_id: u64)]
+pub struct CreateGovernanceProposal<'info> {
+    #[account(
+        init,
+        payer = proposer,
+        // proposal_id, proposer, yes_votes, no_votes, voting_ends_at, finalized, passed,
+        // action_hash, executed
+        space = 8 + 8 + 32 + 8 + 8 + 8 + 1 + 1 + 32 + 1,
+        seeds = [b"proposal", &proposal_id.to_le_bytes()],
+        bump
+    )]
+    pub proposal: Account<'info, GovernanceProposal>,
+
+    #[account(seeds = [b"ve_lock", proposer.key().as_ref()], bump)]
+    pub ve_lock: Account<'info, VeLock>,
+
+    #[account(mut)]
+    pub proposer: Signer<'info>,
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+pub struct CastGovernanceVote<'info> {
+    #[account(
+        mut,
+        seeds = [b"proposal", &proposal.proposal_id.to_le_bytes()],
+        bump
+    )]
+    pub proposal: Account<'info, GovernanceProposal>,
+
+    #[account(seeds = [b"ve_lock", voter.key().as_ref()], bump)]
+    pub ve_lock: Account<'info, VeLock>,
+
+    #[account(
+        init,
+        payer = voter,
+        space = 8 + 32 + 32 + 8 + 1,
+        seeds = [b"governance_vote", proposal.key().as_ref(), voter.key().as_ref()],
+        bump
+    )]
+    pub vote_record: Account<'info, GovernanceVoteRecord>,
+
+    #[account(mut)]
+    pub voter: Signer<'info>,
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+pub struct FinalizeGovernanceProposal<'info> {
+    #[account(
+        mut,
+        seeds = [b"proposal", &proposal.proposal_id.to_le_bytes()],
+        bump
+    )]
+    pub proposal: Account<'info, GovernanceProposal>,
+}
+
+#[derive(Accounts)]
+pub struct InitializeLiquidStaking<'info> {
+    #[account(
+        seeds = [b"global_state"],
+        bump = global_state.bump,
+        has_one = authority @ CustomError::Unauthorized
+    )]
+    pub global_state: Account<'info, GlobalState>,
+
+    #[account(
+        init,
+        payer = authority,
+        space = 8 + 32 + 32 + 2 + 8,
+        seeds = [b"liquid_pool"],
+        bump
+    )]
+    pub liquid_pool: Account<'info, LiquidStakePool>,
+
+    /// stMY receipt mint, same decimals as multiYIELD.
+    #[account(
+        init,
+        payer = authority,
+        seeds = [b"st_mint"],
+        bump,
+        mint::decimals = 6,
+        mint::authority = global_state,
+    )]
+    pub st_mint: InterfaceAccount<'info, Mint>,
+
+    #[account(
+        init,
+        payer = authority,
+        seeds = [b"liquid_vault"],
+        bump,
+        token::mint = mint,
+        token::authority = global_state,
+    )]
+    pub liquid_vault: InterfaceAccount<'info, TokenAccount>,
+
+    #[account(address = global_state.mint)]
+    pub mint: InterfaceAccount<'info, Mint>,
+
+    #[account(mut)]
+    pub authority: Signer<'info>,
+
+    pub system_program: Program<'info, System>,
+    pub token_program: Interface<'info, TokenInterface>,
+    pub rent: Sysvar<'info, Rent>,
+}
+
+#[derive(Accounts)]
+pub struct LiquidStake<'info> {
+    #[account(mut, seeds = [b"liquid_pool"], bump)]
+    pub liquid_pool: Account<'info, LiquidStakePool>,
+
+    #[account(mut, address = liquid_pool.vault)]
+    pub liquid_vault: InterfaceAccount<'info, TokenAccount>,
+
+    #[account(mut, address = liquid_pool.st_mint)]
+    pub st_mint: InterfaceAccount<'info, Mint>,
+
+    #[account(mut, address = global_state.mint)]
+    pub mint: InterfaceAccount<'info, Mint>,
+
+    #[account(mut, seeds = [b"global_state"], bump = global_state.bump)]
+    pub global_state: Account<'info, GlobalState>,
+
+    #[account(mut)]
+    pub staker_token_account: InterfaceAccount<'info, TokenAccount>,
+
+    #[account(mut, token::mint = st_mint)]
+    pub staker_st_account: InterfaceAccount<'info, TokenAccount>,
+
+    pub staker_authority: Signer<'info>,
+    /// Required once rewards are paid from the reward vault instead of minted.
+    #[account(mut, address = global_state.reward_vault @ CustomError::InvalidRewardVault)]
+    pub reward_vault: Option<InterfaceAccount<'info, TokenAccount>>,
+
+    pub token_program: Interface<'info, TokenInterface>,
+}
+
+#[derive(Accounts)]
+pub struct AccrueLiquidRewards<'info> {
+    #[account(mut, seeds = [b"liquid_pool"], bump)]
+    pub liquid_pool: Account<'info, LiquidStakePool>,
+
+    #[account(mut, address = liquid_pool.vault)]
+    pub liquid_vault: InterfaceAccount<'info, TokenAccount>,
+
+    #[account(mut, address = global_state.mint)]
+    pub mint: InterfaceAccount<'info, Mint>,
+
+    #[account(mut, seeds = [b"global_state"], bump = global_state.bump)]
+    pub global_state: Account<'info, GlobalState>,
+
+    /// Required once rewards are paid from the reward vault instead of minted.
+    #[account(mut, address = global_state.reward_vault @ CustomError::InvalidRewardVault)]
+    pub reward_vault: Option<InterfaceAccount<'info, TokenAccount>>,
+
+    pub token_program: Interface<'info, TokenInterface>,
+}
+
+#[derive(Accounts)]
+pub struct StakeNFT<'info> {
+    #[account(
+        init_if_needed,
+        payer = user,
+        space = NFT_STAKE_SPACE,
+        seeds = [b"nft_stake", user.key().as_ref()],
+        bump
+    )]
+    pub nft_stake: Account<'info, NFTStakeAccount>,
+
+    #[account(mut)]
+    pub user: Signer<'info>,
+
+    #[account(
+        constraint = nft_mint.supply == 1 && nft_mint.decimals == 0 @ CustomError::NotAnNFT
+    )]
+    pub nft_mint: InterfaceAccount<'info, Mint>,
+
+    #[account(mut, token::mint = nft_mint, token::authority = user)]
+    pub user_nft_account: InterfaceAccount<'info, TokenAccount>,
+
+    /// Protocol-owned escrow holding the NFT while it is staked.
+    #[account(
+        init,
+        payer = user,
+        seeds = [b"nft_escrow", nft_mint.key().as_ref()],
+        bump,
+        token::mint = nft_mint,
+        token::authority = global_state,
+    )]
+    pub nft_escrow: InterfaceAccount<'info, TokenAccount>,
+
+    #[account(mut, seeds = [b"global_state"], bump = global_state.bump)]
+    pub global_state: Account<'info, GlobalState>,
+
+    /// CHECK: Metaplex metadata PDA for `nft_mint`; contents are parsed in the handler.
+    #[account(
+        seeds = [b"metadata", TOKEN_METADATA_PROGRAM_ID.as_ref(), nft_mint.key().as_ref()],
+        seeds::program = TOKEN_METADATA_PROGRAM_ID,
+        bump,
+        owner = TOKEN_METADATA_PROGRAM_ID
+    )]
+    pub nft_metadata: UncheckedAccount<'info>,
+
+    /// Allowlist entry for the NFT's verified collection.
+    #[account(seeds = [b"collection", collection_config.collection_mint.as_ref()], bump)]
+    pub collection_config: Account<'info, CollectionConfig>,
+
+    /// Additional feed to check NFT floor price
+    #[account(address = collection_config.floor_price_feed @ CustomError::OracleError)]
+    pub nft_floor_price_feed: AccountInfo<'info>,
+
+    /// Collection tier authority, when it attests the NFT's tier instead of a merkle proof.
+    pub tier_authority: Option<Signer<'info>>,
+
+    pub system_program: Program<'info, System>,
+    pub token_program: Interface<'info, TokenInterface>,
+    pub rent: Sysvar<'info, Rent>,
+}
+
+#[derive(Accounts)]
+pub struct RefreshNFTBoost<'info> {
+    #[account(mut, seeds = [b"nft_stake", user.key().as_ref()], bump)]
+    pub nft_stake: Account<'info, NFTStakeAccount>,
+
+    #[account(seeds = [b"collection", collection_config.collection_mint.as_ref()], bump)]
+    pub collection_config: Account<'info, CollectionConfig>,
+
+    /// CHECK: must be the collection's configured floor feed.
+    #[account(address = collection_config.floor_price_feed @ CustomError::OracleError)]
+    pub nft_floor_price_feed: UncheckedAccount<'info>,
+
+    pub user: Signer<'info>,
+}
+
+#[derive(Accounts)]
+pub struct UnstakeNFT<'info> {
+    #[account(
+        mut,
+        seeds = [b"nft_stake", user.key().as_ref()],
+        bump
+    )]
+    pub nft_stake: Account<'info, NFTStakeAccount>,
+
+    pub nft_mint: InterfaceAccount<'info, Mint>,
+
+    #[account(mut, token::mint = nft_mint, token::authority = user)]
+    pub user_nft_account: InterfaceAccount<'info, TokenAccount>,
+
+    #[account(mut, seeds = [b"nft_escrow", nft_mint.key().as_ref()], bump)]
+    pub nft_escrow: InterfaceAccount<'info, TokenAccount>,
+
+    #[account(mut, seeds = [b"global_state"], bump = global_state.bump)]
+    pub global_state: Account<'info, GlobalState>,
+
+    #[account(mut)]
+    pub user: Signer<'info>,
+    pub token_program: Interface<'info, TokenInterface>,
+}
+
+#[derive(Accounts)]
+pub struct InsurancePoolContribution<'info> {
+    #[account(mut)]
+    pub contributor_token_account: InterfaceAccount<'info, TokenAccount>,
+
+    #[account(mut)]
+    pub insurance_pool_account: InterfaceAccount<'info, TokenAccount>,
+
+    #[account(mut)]
+    pub contributor_authority: Signer<'info>,
+
+    #[account(address = insurance_pool_account.mint)]
+    pub mint: InterfaceAccount<'info, Mint>,
+
+    pub token_program: Interface<'info, TokenInterface>,
+}
+
+#[derive(Accounts)]
+pub struct StakeLPTokens<'info> {
+    #[account(
+        init_if_needed,
+        payer = staker_authority,
+        // owner, lp_mint, lp_staked, reward_multiplier, reward_debt, pending_rewards, last_claim_time
+        space = 8 + 32 + 32 + 8 + 1 + 16 + 8 + 8,
+        seeds = [b"lp_stake", lp_pool.lp_mint.as_ref(), staker_authority.key().as_ref()],
+        bump
+    )]
+    pub lp_stake: Account<'info, LpStakeAccount>,
+
+    #[account(mut, seeds = [b"lp_pool", lp_pool.lp_mint.as_ref()], bump)]
+    pub lp_pool: Account<'info, LpPool>,
+
+    #[account(seeds = [b"lp_controller"], bump)]
+    pub lp_controller: Account<'info, LpController>,
+
+    #[account(mut, token::mint = lp_pool.lp_mint)]
+    pub lp_token_account: InterfaceAccount<'info, TokenAccount>,
+
+    #[account(mut, address = lp_pool.vault)]
+    pub staking_pool_lp_account: InterfaceAccount<'info, TokenAccount>,
+
+    #[account(mut)]
+    pub staker_authority: Signer<'info>,
+
+    #[account(address = lp_token_account.mint)]
+    pub lp_mint: InterfaceAccount<'info, Mint>,
+
+    #[account(address = lp_pool.reserve_a)]
+    pub reserve_a: InterfaceAccount<'info, TokenAccount>,
+
+    #[account(address = lp_pool.reserve_b)]
+    pub reserve_b: InterfaceAccount<'info, TokenAccount>,
+
+    /// CHECK: Pyth feed for the first leg, pinned by the pool config.
+    #[account(address = lp_pool.price_feed_a @ CustomError::OracleError)]
+    pub price_feed_a: UncheckedAccount<'info>,
+
+    /// CHECK: Pyth feed for the second leg, pinned by the pool config.
+    #[account(address = lp_pool.price_feed_b @ CustomError::OracleError)]
+    pub price_feed_b: UncheckedAccount<'info>,
+
+    pub token_program: Interface<'info, TokenInterface>,
+    pub system_program: Program<'info, System>,
+    pub rent: Sysvar<'info, Rent>,
+}
+
+#[derive(Accounts)]
+pub struct ClaimLPRewards<'info> {
+    #[account(
+        mut,
+        seeds = [b"lp_stake", lp_pool.lp_mint.as_ref(), staker_authority.key().as_ref()],
+        bump
+    )]
+    pub lp_stake: Account<'info, LpStakeAccount>,
+
+    #[account(mut, seeds = [b"lp_pool", lp_pool.lp_mint.as_ref()], bump)]
+    pub lp_pool: Account<'info, LpPool>,
+
+    #[account(seeds = [b"lp_controller"], bump)]
+    pub lp_controller: Account<'info, LpController>,
+
+    #[account(mut)]
+    pub lp_reward_account: InterfaceAccount<'info, TokenAccount>,
+
+    #[account(
+        mut,
+        seeds = [b"global_state"],
+        bump = global_state.bump
+    )]
+    pub global_state: Account<'info, GlobalState>,
+
+    #[account(seeds = [b"governance"], bump)]
+    pub governance: Account<'info, Governance>,
+
+    #[account(mut)]
+    pub mint: InterfaceAccount<'info, Mint>,
+
+    pub staker_authority: Signer<'info>,
+    /// Required once rewards are paid from the reward vault instead of minted.
+    #[account(mut, address = global_state.reward_vault @ CustomError::InvalidRewardVault)]
+    pub reward_vault: Option<InterfaceAccount<'info, TokenAccount>>,
+
+    pub token_program: Interface<'info, TokenInterface>,
+}
+
+#[derive(Accounts)]
+pub struct InitializeLpController<'info> {
+    #[account(
+        seeds = [b"global_state"],
+        bump = global_state.bump,
+        has_one = authority @ CustomError::Unauthorized
+    )]
+    pub global_state: Account<'info, GlobalState>,
+
+    #[account(
+        init,
+        payer = authority,
+        // reward_per_second, total_alloc_point, pool_count
+        space = 8 + 8 + 8 + 4,
+        seeds = [b"lp_controller"],
+        bump
+    )]
+    pub lp_controller: Account<'info, LpController>,
+
+    #[account(mut)]
+    pub authority: Signer<'info>,
+
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+pub struct InitializeLpPool<'info> {
+    #[account(
+        seeds = [b"global_state"],
+        bump = global_state.bump,
+        has_one = authority @ CustomError::Unauthorized
+    )]
+    pub global_state: Account<'info, GlobalState>,
+
+    #[account(
+        init,
+        payer = authority,
+        // lp_mint, vault, alloc_point, acc_reward_per_share, total_weight, last_update,
+        // reserve_a, reserve_b, price_feed_a, price_feed_b, decimals_a, decimals_b
+        space = 8 + 32 + 32 + 8 + 16 + 8 + 8 + 32 * 4 + 1 + 1,
+        seeds = [b"lp_pool", lp_mint.key().as_ref()],
+        bump
+    )]
+    pub lp_pool: Account<'info, LpPool>,
+
+    #[account(mut, seeds = [b"lp_controller"], bump)]
+    pub lp_controller: Account<'info, LpController>,
+
+    #[account(
+        init,
+        payer = authority,
+        seeds = [b"lp_vault", lp_mint.key().as_ref()],
+        bump,
+        token::mint = lp_mint,
+        token::authority = global_state,
+    )]
+    pub lp_vault: InterfaceAccount<'info, TokenAccount>,
+
+    pub lp_mint: InterfaceAccount<'info, Mint>,
+
+    #[account(mut)]
+    pub authority: Signer<'info>,
+
+    pub system_program: Program<'info, System>,
+    pub token_program: Interface<'info, TokenInterface>,
+    pub rent: Sysvar<'info, Rent>,
+}
+
+#[derive(Accounts)]
+pub struct UnstakeLPTokens<'info> {
+    #[account(mut, seeds = [b"lp_stake", lp_pool.lp_mint.as_ref(), staker_authority.key().as_ref()], bump)]
+    pub lp_stake: Account<'info, LpStakeAccount>,
+
+    #[account(mut, seeds = [b"lp_pool", lp_pool.lp_mint.as_ref()], bump)]
+    pub lp_pool: Account<'info, LpPool>,
+
+    #[account(seeds = [b"lp_controller"], bump)]
+    pub lp_controller: Account<'info, LpController>,
+
+    #[account(mut, address = lp_pool.vault)]
+    pub lp_vault: InterfaceAccount<'info, TokenAccount>,
+
+    #[account(address = lp_pool.lp_mint)]
+    pub lp_mint: InterfaceAccount<'info, Mint>,
+
+    #[account(mut, token::mint = lp_pool.lp_mint)]
+    pub lp_token_account: InterfaceAccount<'info, TokenAccount>,
+
+    #[account(seeds = [b"global_state"], bump = global_state.bump)]
+    pub global_state: Account<'info, GlobalState>,
+
+    pub staker_authority: Signer<'info>,
+
+    #[account(address = lp_pool.reserve_a)]
+    pub reserve_a: InterfaceAccount<'info, TokenAccount>,
+
+    #[account(address = lp_pool.reserve_b)]
+    pub reserve_b: InterfaceAccount<'info, TokenAccount>,
+
+    /// CHECK: Pyth feed for the first leg, pinned by the pool config.
+    #[account(address = lp_pool.price_feed_a @ CustomError::OracleError)]
+    pub price_feed_a: UncheckedAccount<'info>,
+
+    /// CHECK: Pyth feed for the second leg, pinned by the pool config.
+    #[account(address = lp_pool.price_feed_b @ CustomError::OracleError)]
+    pub price_feed_b: UncheckedAccount<'info>,
+
+    pub token_program: Interface<'info, TokenInterface>,
+}
+
+#[derive(Accounts)]
+pub struct RepriceLpStake<'info> {
+    #[account(mut, seeds = [b"lp_stake", lp_pool.lp_mint.as_ref(), lp_stake.owner.as_ref()], bump)]
+    pub lp_stake: Account<'info, LpStakeAccount>,
+
+    #[account(mut, seeds = [b"lp_pool", lp_pool.lp_mint.as_ref()], bump)]
+    pub lp_pool: Account<'info, LpPool>,
+
+    #[account(seeds = [b"lp_controller"], bump)]
+    pub lp_controller: Account<'info, LpController>,
+
+    #[account(address = lp_pool.lp_mint)]
+    pub lp_mint: InterfaceAccount<'info, Mint>,
+
+    #[account(address = lp_pool.reserve_a)]
+    pub reserve_a: InterfaceAccount<'info, TokenAccount>,
+
+    #[account(address = lp_pool.reserve_b)]
+    pub reserve_b: InterfaceAccount<'info, TokenAccount>,
+
+    /// CHECK: Pyth feed for the first leg, pinned by the pool config.
+    #[account(address = lp_pool.price_feed_a @ CustomError::OracleError)]
+    pub price_feed_a: UncheckedAccount<'info>,
+
+    /// CHECK: Pyth feed for the second leg, pinned by the pool config.
+    #[account(address = lp_pool.price_feed_b @ CustomError::OracleError)]
+    pub price_feed_b: UncheckedAccount<'info>,
+}
+
+#[derive(Accounts)]
+pub struct WithdrawLegacyLp<'info> {
+    /// CHECK: LP stake in the original layout; parsed and closed in the handler.
+    #[account(mut, seeds = [b"lp_stake", staker_authority.key().as_ref()], bump, owner = crate::ID)]
+    pub legacy_lp_stake: UncheckedAccount<'info>,
+
+    #[account(mut, address = global_state.legacy_lp_vault @ CustomError::InvalidLegacyLpVault)]
+    pub legacy_lp_vault: InterfaceAccount<'info, TokenAccount>,
+
+    #[account(address = legacy_lp_vault.mint)]
+    pub lp_mint: InterfaceAccount<'info, Mint>,
+
+    #[account(mut, token::mint = lp_mint)]
+    pub lp_token_account: InterfaceAccount<'info, TokenAccount>,
+
+    #[account(seeds = [b"global_state"], bump = global_state.bump)]
+    pub global_state: Account<'info, GlobalState>,
+
+    #[account(mut)]
+    pub staker_authority: Signer<'info>,
+
+    pub token_program: Interface<'info, TokenInterface>,
+}
+
+#[derive(Accounts)]
+pub struct SetLpPoolPricing<'info> {
+    #[account(
+        seeds = [b"global_state"],
+        bump = global_state.bump,
+        has_one = authority @ CustomError::Unauthorized
+    )]
+    pub global_state: Account<'info, GlobalState>,
+
+    #[account(mut, seeds = [b"lp_pool", lp_pool.lp_mint.as_ref()], bump)]
+    pub lp_pool: Account<'info, LpPool>,
+
+    pub reserve_a: InterfaceAccount<'info, TokenAccount>,
+    #[account(constraint = reserve_b.mint != reserve_a.mint @ CustomError::InvalidRewardParameters)]
+    pub reserve_b: InterfaceAccount<'info, TokenAccount>,
+
+    #[account(address = reserve_a.mint)]
+    pub mint_a: InterfaceAccount<'info, Mint>,
+    #[account(address = reserve_b.mint)]
+    pub mint_b: InterfaceAccount<'info, Mint>,
+
+    pub authority: Signer<'info>,
+}
+
+#[derive(Accounts)]
+pub struct SetLpRewardRate<'info> {
+    #[account(
+        seeds = [b"global_state"],
+        bump = global_state.bump,
+        has_one = authority @ CustomError::Unauthorized
+    )]
+    pub global_state: Account<'info, GlobalState>,
+
+    #[account(mut, seeds = [b"lp_controller"], bump)]
+    pub lp_controller: Account<'info, LpController>,
+
+    pub authority: Signer<'info>,
+}
+
+#[derive(Accounts)]
+pub struct SetLpPoolAlloc<'info> {
+    #[account(
+        seeds = [b"global_state"],
+        bump = global_state.bump,
+        has_one = authority @ CustomError::Unauthorized
+    )]
+    pub global_state: Account<'info, GlobalState>,
+
+    /// Passed proposal authorizing exactly this change.
+    #[account(mut, seeds = [b"proposal", &proposal.proposal_id.to_le_bytes()], bump)]
+    pub proposal: Account<'info, GovernanceProposal>,
+
+    #[account(mut, seeds = [b"lp_controller"], bump)]
+    pub lp_controller: Account<'info, LpController>,
+
+    #[account(mut, seeds = [b"lp_pool", lp_pool.lp_mint.as_ref()], bump)]
+    pub lp_pool: Account<'info, LpPool>,
+
+    pub authority: Signer<'info>,
+}
+
+#[derive(Accounts)]
+pub struct InitializeGaugeController<'info> {
+    #[account(
+        seeds = [b"global_state"],
+        bump = global_state.bump,
+        has_one = authority @ CustomError::Unauthorized
+    )]
+    pub global_state: Account<'info, GlobalState>,
+
+    #[account(
+        init,
+        payer = authority,
+        // gauges, epoch, epoch_start, epoch_duration
+        space = 8 + (4 + 32 * MAX_GAUGES) + 8 + 8 + 8,
+        seeds = [b"gauge_controller"],
+        bump
+    )]
+    pub gauge_controller: Account<'info, GaugeController>,
+
+    #[account(
+        init,
+        payer = authority,
+        // epoch, gauges, votes, total
+        space = 8 + 8 + (4 + 32 * MAX_GAUGES) + (4 + 8 * MAX_GAUGES) + 8,
+        seeds = [b"gauge_tally", 0u64.to_le_bytes().as_ref()],
+        bump
+    )]
+    pub tally: Account<'info, GaugeTally>,
+
+    #[account(mut)]
+    pub authority: Signer<'info>,
+
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+pub struct SetGaugeMints<'info> {
+    #[account(
+        seeds = [b"global_state"],
+        bump = global_state.bump,
+        has_one = authority @ CustomError::Unauthorized
+    )]
+    pub global_state: Account<'info, GlobalState>,
+
+    /// Passed proposal authorizing exactly this change.
+    #[account(mut, seeds = [b"proposal", &proposal.proposal_id.to_le_bytes()], bump)]
+    pub proposal: Account<'info, GovernanceProposal>,
+
+    #[account(mut, seeds = [b"gauge_controller"], bump)]
+    pub gauge_controller: Account<'info, GaugeController>,
+
+    #[account(seeds = [b"lp_controller"], bump)]
+    pub lp_controller: Account<'info, LpController>,
+
+    pub authority: Signer<'info>,
+}
+
+#[derive(Accounts)]
+pub struct VoteGaugeWeights<'info> {
+    #[account(seeds = [b"gauge_controller"], bump)]
+    pub gauge_controller: Account<'info, GaugeController>,
+
+    #[account(mut, seeds = [b"gauge_tally", gauge_controller.epoch.to_le_bytes().as_ref()], bump)]
+    pub tally: Account<'info, GaugeTally>,
+
+    #[account(seeds = [b"ve_lock", voter.key().as_ref()], bump)]
+    pub ve_lock: Account<'info, VeLock>,
+
+    #[account(
+        init_if_needed,
+        payer = voter,
+        // voter, epoch, votes
+        space = 8 + 32 + 8 + (4 + 8 * MAX_GAUGES),
+        seeds = [b"gauge_vote", voter.key().as_ref()],
+        bump
+    )]
+    pub gauge_vote: Account<'info, GaugeVote>,
+
+    #[account(mut)]
+    pub voter: Signer<'info>,
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+pub struct RollGaugeEpoch<'info> {
+    #[account(mut, seeds = [b"gauge_controller"], bump)]
+    pub gauge_controller: Account<'info, GaugeController>,
+
+    #[account(seeds = [b"gauge_tally", gauge_controller.epoch.to_le_bytes().as_ref()], bump)]
+    pub tally: Account<'info, GaugeTally>,
+
+    #[account(
+        init,
+        payer = payer,
+        space = 8 + 8 + (4 + 32 * MAX_GAUGES) + (4 + 8 * MAX_GAUGES) + 8,
+        seeds = [b"gauge_tally", (gauge_controller.epoch + 1).to_le_bytes().as_ref()],
+        bump
+    )]
+    pub next_tally: Account<'info, GaugeTally>,
+
+    #[account(mut, seeds = [b"lp_controller"], bump)]
+    pub lp_controller: Account<'info, LpController>,
+
+    #[account(mut)]
+    pub payer: Signer<'info>,
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+pub struct UpdateGovernance<'info> {
+    #[account(
+        seeds = [b"global_state"],
+        bump = global_state.bump,
+        has_one = authority @ CustomError::Unauthorized,
+        has_one = governance
+    )]
+    pub global_state: Account<'info, GlobalState>,
+
+    #[account(mut, seeds = [b"governance"], bump)]
+    pub governance: Account<'info, Governance>,
+
+    /// Passed proposal authorizing exactly this change.
+    #[account(mut, seeds = [b"proposal", &proposal.proposal_id.to_le_bytes()], bump)]
+    pub proposal: Account<'info, GovernanceProposal>,
+
+    pub authority: Signer<'info>,
+}
+
+#[derive(Accounts)]
+#[instruction(collection_mint: Pubkey)]
+pub struct SetCollectionConfig<'info> {
+    #[account(
+        seeds = [b"global_state"],
+        bump = global_state.bump,
+        has_one = authority @ CustomError::Unauthorized
+    )]
+    pub global_state: Account<'info, GlobalState>,
+
+    /// Passed proposal authorizing exactly this change.
+    #[account(mut, seeds = [b"proposal", &proposal.proposal_id.to_le_bytes()], bump)]
+    pub proposal: Account<'info, GovernanceProposal>,
+
+    #[account(
+        init_if_needed,
+        payer = authority,
+        space = 8 + 32 + 32 + 8 + 2 + 32 + 32 + 2 * 4 + 8,
+        seeds = [b"collection", collection_mint.as_ref()],
+        bump
+    )]
+    pub collection_config: Account<'info, CollectionConfig>,
+
+    #[account(mut)]
+    pub authority: Signer<'info>,
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+pub struct UpdateCollectionConfig<'info> {
+    #[account(
+        seeds = [b"global_state"],
+        bump = global_state.bump,
+        has_one = authority @ CustomError::Unauthorized
+    )]
+    pub global_state: Account<'info, GlobalState>,
+
+    /// Passed proposal authorizing exactly this change.
+    #[account(mut, seeds = [b"proposal", &proposal.proposal_id.to_le_bytes()], bump)]
+    pub proposal: Account<'info, GovernanceProposal>,
+
+    #[account(mut, seeds = [b"collection", collection_config.collection_mint.as_ref()], bump)]
+    pub collection_config: Account<'info, CollectionConfig>,
+
+    pub authority: Signer<'info>,
+}
+
+#[derive(Accounts)]
+pub struct RemoveCollectionConfig<'info> {
+    #[account(
+        seeds = [b"global_state"],
+        bump = global_state.bump,
+        has_one = authority @ CustomError::Unauthorized
+    )]
+    pub global_state: Account<'info, GlobalState>,
+
+    /// Passed proposal authorizing exactly this change.
+    #[account(mut, seeds = [b"proposal", &proposal.proposal_id.to_le_bytes()], bump)]
+    pub proposal: Account<'info, GovernanceProposal>,
+
+    #[account(
+        mut,
+        close = authority,
+        seeds = [b"collection", collection_config.collection_mint.as_ref()],
+        bump
+    )]
+    pub collection_config: Account<'info, CollectionConfig>,
+
+    #[account(mut)]
+    pub authority: Signer<'info>,
+}
+
+// -----------------------------------------------
+//                State Structures
+// -----------------------------------------------
+#[account]
+pub struct GlobalState {
+    pub mint: Pubkey,
+    pub bump: u8,
+    pub protocol_wide_volume: u64, // track overall volume
+    pub authority: Pubkey,         // protocol admin
+    pub unstake_cooldown: i64,     // seconds before an unstake ticket is claimable
+    pub total_staked: u64,         // fee-earning stake: bonded, position and veMY amounts
+    pub max_nft_boost_bps: u16,    // cap on stacked NFT boosts
+    pub fee_destination: Pubkey,   // token account receiving harvested Token-2022 transfer fees
+    pub max_supply: u64,           // hard cap on multiYIELD supply
+    pub minted: EmissionTotals,    // lifetime emissions by category
+    pub reward_vault: Pubkey,      // pre-funded rewards vault; default = mint rewards instead
+    pub reward_vault_since: i64,   // when vault mode was switched on
+    pub vault_distributed: u64,    // rewards paid out of the vault so far
+    pub buyback_amm_program: Pubkey, // whitelisted AMM for buyback swaps
+    pub buyback_price_feed: Pubkey,  // Pyth feed bounding buyback output
+    pub buyback_max_slippage_bps: u16,
+    pub total_burned: u64,         // multiYIELD burned through buybacks
+    pub governance: Pubkey,        // canonical governance PDA
+    pub dao_treasury: Pubkey,      // token account receiving early-exit penalties
+    pub buyback_authority_bump: u8, // PDA signing buyback swaps; owns only buyback accounts
+    pub legacy_lp_vault: Pubkey,   // token account the original program's LP stakes were paid into
+    pub legacy_lp_root: [u8; 32],  // merkle root of audited legacy LP balances; zero until committed
+    pub legacy_stake_root: [u8; 32], // merkle root of audited legacy stake balances; zero until committed
+}
+
+/// Lifetime multiYIELD minted per emission category.
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Copy, Default)]
+pub struct EmissionTotals {
+    pub trading: u64,
+    pub staking: u64,
+    pub lp: u64,
+    pub insurance: u64,
+    pub treasury: u64,
+}
+
+impl EmissionTotals {
+    pub fn record(&mut self, category: Emission, amount: u64) {
+        let bucket = match category {
+            Emission::Trading => &mut self.trading,
+            Emission::Staking => &mut self.staking,
+            Emission::Lp => &mut self.lp,
+            Emission::Insurance => &mut self.insurance,
+            Emission::Treasury => &mut self.treasury,
+        };
+        *bucket = bucket.saturating_add(amount);
+    }
+}
+
+/// Reward vault status returned by `reward_vault_runway`.
+#[derive(AnchorSerialize, AnchorDeserialize, Clone)]
+pub struct RewardRunway {
+    pub balance: u64,
+    pub distributed: u64,
+    pub runway_seconds: Option<i64>,
+}
+
+/// What a multiYIELD emission pays for.
+#[derive(Clone, Copy)]
+pub enum Emission {
+    Trading,
+    Staking,
+    Lp,
+    Insurance,
+    Treasury,
+}
+
+#[account]
+pub struct TraderVolume {
+    pub trader: Pubkey,
+    pub total_volume: u64,
+    pub last_trade_time: i64,
+}
+
+#[account]
+pub struct StakeAccount {
+    pub owner: Pubkey,
+    pub amount: u64,
+    pub stake_timestamp: i64,
+    pub last_claim_time: i64,
+    pub delegate: Pubkey, // may claim on the owner's behalf, never withdraw; default if none
+}
+
+impl StakeAccount {
+    /// Whether `signer` may claim this stake's rewards: the owner or the current delegate.
+    pub fn can_claim(&self, signer: &Pubkey) -> bool {
+        *signer == self.owner || (self.delegate != Pubkey::default() && *signer == self.delegate)
+    }
+}
+
+/// Pending withdrawal out of a `StakeAccount`; earns nothing while it waits.
+#[account]
+pub struct UnstakeTicket {
+    pub owner: Pubkey,
+    pub ticket_id: u64,
+    pub amount: u64,
+    pub requested_at: i64,
+    pub claimable_at: i64,
+}
+
+/// Real-yield vault for one quote mint; its accumulator lives at `index` in the `FeeRegistry`.
+#[account]
+pub struct FeeVault {
+    pub quote_mint: Pubkey,
+    pub vault: Pubkey,
+    pub index: u8,
+    pub total_deposited: u64,
+}
+
+/// Per-share fee accumulators of every fee vault, scaled by `FEE_PRECISION`.
+#[account]
+pub struct FeeRegistry {
+    pub acc_fee_per_share: [u128; MAX_FEE_VAULTS],
+    pub vault_count: u8,
+}
+
+/// Fee share of one fee-earning stake (`StakeAccount`, `StakePosition` or `VeLock`) across all
+/// fee vaults, using the reward-debt model: `shares` mirrors the holder's amount.
+#[account]
+pub struct FeeClaim {
+    pub owner: Pubkey,
+    pub holder: Pubkey,
+    pub shares: u64,
+    pub reward_debt: [u128; MAX_FEE_VAULTS], // shares * acc at the last settlement
+    pub pending: [u64; MAX_FEE_VAULTS],      // settled but not yet claimed
+}
+
+impl FeeClaim {
+    /// Credit fees earned on the current `shares` since the last settlement, then re-base the
+    /// claim on `new_shares` and move `total_staked` by the difference. Every change to a
+    /// fee-earning amount goes through here first.
+    pub fn settle(
+        &mut self,
+        acc_fee_per_share: &[u128; MAX_FEE_VAULTS],
+        total_staked: &mut u64,
+        new_shares: u64,
+    ) -> Result<()> {
+        for (i, acc) in acc_fee_per_share.iter().enumerate() {
+            let earned = fee_reward_debt(self.shares, *acc).saturating_sub(self.reward_debt[i]);
+            let earned: u64 = earned.try_into().map_err(|_| CustomError::ConversionError)?;
+            self.pending[i] = self.pending[i].checked_add(earned).ok_or(CustomError::ArithmeticOverflow)?;
+            self.reward_debt[i] = fee_reward_debt(new_shares, *acc);
+        }
+        *total_staked = total_staked
+            .saturating_sub(self.shares)
+            .checked_add(new_shares)
+            .ok_or(CustomError::ArithmeticOverflow)?;
+        self.shares = new_shares;
+        Ok(())
+    }
+}
+
+/// One of several independently locked stake positions held by `owner`.
+#[account]
+pub struct StakePosition {
+    pub owner: Pubkey,
+    pub position_id: u64,
+    pub amount: u64,
+    pub stake_timestamp: i64,
+    pub lock_until: i64,
+    pub auto_compound: bool,
+    pub nft_stake: Pubkey, // linked NFTStakeAccount, default if unboosted
+    pub last_claim_time: i64,
+}
+
+/// Vote-escrowed multiYIELD: power decays linearly to zero at `lock_end`.
+#[account]
+pub struct VeLock {
+    pub owner: Pubkey,
+    pub amount: u64,
+    pub lock_start: i64,
+    pub lock_end: i64,
+}
+
+/// A veMY vote on a governance proposal, tallied into `yes_votes`/`no_votes` until `voting_ends_at`.
+#[account]
+pub struct GovernanceProposal {
+    pub proposal_id: u64,
+    pub proposer: Pubkey,
+    pub yes_votes: u64,
+    pub no_votes: u64,
+    pub voting_ends_at: i64,
+    pub finalized: bool,
+    pub passed: bool,
+    pub action_hash: [u8; 32], // governance_action_hash of the change this proposal authorizes
+    pub executed: bool,
+}
+
+/// Per-proposal vote receipt.
+#[account]
+pub struct GovernanceVoteRecord {
+    pub voter: Pubkey,
+    pub proposal: Pubkey,
+    pub weight: u64,
+    pub support: bool,
+}
+
+/// Pooled liquid staking: stMY supply is a claim on `vault`, which grows as rewards accrue.
+#[account]
+pub struct LiquidStakePool {
+    pub st_mint: Pubkey,
+    pub vault: Pubkey,
+    pub reward_rate_bps: u16, // annual rate minted into the vault
+    pub last_accrual: i64,
+}
+
+#[account]
+pub struct NFTStakeAccount {
+    pub owner: Pubkey,
+    pub nfts: Vec<StakedNft>, // at most MAX_STAKED_NFTS
+}
+
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Default)]
+pub struct StakedNft {
+    pub mint: Pubkey,
+    pub collection: Pubkey,
+    pub tier: u8,               // rarity tier, 0 for the collection's base boost
+    pub boost_bps: u16,         // base + tier boost, fixed at stake time
+    pub boost_expires_at: i64,  // boosts must be refreshed by this time
+}
+
+impl NFTStakeAccount {
+    /// Combined reward boost of all staked NFTs in basis points, capped at `max_bps`.
+    /// Expired boosts count as zero, and so do boosts from collections missing from
+    /// `floor_scales`: each boost is scaled by its collection's fresh floor check.
+    pub fn active_boost_bps(&self, max_bps: u16, now: i64, floor_scales: &[(Pubkey, u64)]) -> u64 {
+        let boosts: Vec<u16> = self
+            .nfts
+            .iter()
+            .map(|nft| {
+                if now >= nft.boost_expires_at {
+                    return 0;
+                }
+                let scale_bps = floor_scales
+                    .iter()
+                    .find(|(collection, _)| *collection == nft.collection)
+                    .map_or(0, |(_, scale_bps)| *scale_bps);
+                ((nft.boost_bps as u64 * scale_bps) / 10_000) as u16
+            })
+            .collect();
+        stacked_nft_boost_bps(&boosts, max_bps)
+    }
+
+    /// Drop a staked NFT and its boost, e.g. when it leaves escrow.
+    pub fn remove_nft(&mut self, mint: &Pubkey) -> Result<StakedNft> {
+        let index = self
+            .nfts
+            .iter()
+            .position(|nft| nft.mint == *mint)
+            .ok_or(CustomError::NFTNotStaked)?;
+        Ok(self.nfts.swap_remove(index))
+    }
+}
+
+/// Governance-allowlisted NFT collection.
+#[account]
+pub struct CollectionConfig {
+    pub collection_mint: Pubkey,
+    pub floor_price_feed: Pubkey,
+    pub min_floor_price: i64,
+    pub boost_bps: u16,
+    pub tier_authority: Pubkey,     // may co-sign tier claims; default if unused
+    pub tier_merkle_root: [u8; 32], // root of keccak(mint, tier) leaves; zero if unused
+    pub tier_boost_bps: [u16; 4],   // extra boost for tiers 1..=4
+    pub boost_duration: i64,        // seconds a boost lasts before it must be refreshed
+}
+
+#[account]
+pub struct VestingSchedule {
+    pub owner: Pubkey,
+    pub total_reward: u64,
+    pub claimed: u64,
+    pub start_time: i64,
+    pub duration: i64,
+}
+
+#[account]
+pub struct LpStakeAccount {
+    pub owner: Pubkey,
+    pub lp_mint: Pubkey,
+    pub lp_staked: u64,
+    pub reward_multiplier: u8, // USD-value tier, repriced on stake, unstake and reprice
+    pub reward_debt: u128,     // weight * acc_reward_per_share at the last settlement
+    pub pending_rewards: u64,  // settled but not yet claimed
+    pub last_claim_time: i64,
+}
+
+impl LpStakeAccount {
+    /// Share of the pool this stake earns: LP staked times its tier multiplier.
+    pub fn weight(&self) -> u64 {
+        self.lp_staked.saturating_mul(self.reward_multiplier as u64)
+    }
+}
+
+/// Total LP emission rate, split across `LpPool`s by allocation points.
+#[account]
+pub struct LpController {
+    pub reward_per_second: u64,
+    pub total_alloc_point: u64,
+    pub pool_count: u32, // LpPools created so far; rate and allocation changes must update them all
+}
+
+/// Governance-maintained gauge list and the current voting epoch.
+#[account]
+pub struct GaugeController {
+    pub gauges: Vec<Pubkey>, // LP mints eligible for votes from the next epoch on
+    pub epoch: u64,
+    pub epoch_start: i64,
+    pub epoch_duration: i64,
+}
+
+/// Checkpointed vote totals for one epoch; `gauges` is the list frozen when the epoch opened.
+#[account]
+pub struct GaugeTally {
+    pub epoch: u64,
+    pub gauges: Vec<Pubkey>,
+    pub votes: Vec<u64>,
+    pub total: u64,
+}
+
+/// A voter's latest gauge vote, kept so a re-vote in the same epoch can be backed out.
+#[account]
+pub struct GaugeVote {
+    pub voter: Pubkey,
+    pub epoch: u64,
+    pub votes: Vec<u64>,
+}
+
+/// One LP mint's gauge; `acc_reward_per_share` is per unit of stake weight, scaled by `LP_ACC_PRECISION`.
+#[account]
+pub struct LpPool {
+    pub lp_mint: Pubkey,
+    pub vault: Pubkey,
+    pub alloc_point: u64,
+    pub acc_reward_per_share: u128,
+    pub total_weight: u64,
+    pub last_update: i64,
+    pub reserve_a: Pubkey,    // AMM reserve token accounts used to value the LP token
+    pub reserve_b: Pubkey,
+    pub price_feed_a: Pubkey, // Pyth USD feeds for each leg
+    pub price_feed_b: Pubkey,
+    pub decimals_a: u8,
+    pub decimals_b: u8,
+}
+
+#[account]
+pub struct Governance {
+    pub reward_percentage: u8, // base reward percentage
+    pub lp_boost: u8,          // additional boost for LP rewards
+}
+
+// -----------------------------------------------
+//                    Helpers
+// -----------------------------------------------
+
+/// Weighted-average entry time after adding `added_amount` at `now` to an existing stake.
+/// Each token keeps its own age, so a top-up neither wipes loyalty progress nor lets a
+/// tiny early deposit carry a large later one into a higher tier.
+pub fn weighted_stake_timestamp(
+    existing_amount: u64,
+    existing_timestamp: i64,
+    added_amount: u64,
+    now: i64,
+) -> Result<i64> {
+    if existing_amount == 0 {
+        return Ok(now);
+    }
+    let total = (existing_amount as i128)
+        .checked_add(added_amount as i128)
+        .ok_or(CustomError::ArithmeticOverflow)?;
+    let weighted = (existing_amount as i128)
+        .checked_mul(existing_timestamp as i128)
+        .and_then(|v| v.checked_add((added_amount as i128).checked_mul(now as i128)?))
+        .ok_or(CustomError::ArithmeticOverflow)?;
+    (weighted / total)
+        .try_into()
+        .map_err(|_| CustomError::ConversionError.into())
+}
+
+/// Last-claim time after `withdrawn` of `amount` leaves a stake: the rest takes over the amount
+/// x seconds accrued since `last_claim_time`, so the next claim still pays for the withdrawn
+/// tokens. Withdrawing the whole stake needs its rewards claimed first.
+pub fn claim_time_after_withdrawal(amount: u64, last_claim_time: i64, withdrawn: u64, now: i64) -> Result<i64> {
+    let elapsed = now.saturating_sub(last_claim_time);
+    if elapsed <= 0 || withdrawn == 0 {
+        return Ok(last_claim_time);
+    }
+    let remaining = amount.checked_sub(withdrawn).ok_or(CustomError::InsufficientStake)?;
+    require!(remaining > 0, CustomError::UnclaimedRewards);
+    let stretched = elapsed as i128 * amount as i128 / remaining as i128;
+    (now as i128 - stretched)
+        .try_into()
+        .map_err(|_| CustomError::ArithmeticOverflow.into())
+}
+
+/// veMY voting power: `amount` scaled by the remaining lock over the 4-year maximum.
+pub fn ve_voting_power(amount: u64, lock_end: i64, now: i64) -> u64 {
+    let remaining = lock_end.saturating_sub(now).clamp(0, VE_MAX_LOCK);
+    ((amount as u128 * remaining as u128) / VE_MAX_LOCK as u128) as u64
+}
+
+/// A proposal passes on a strict majority of the veMY power that voted on it, once at least
+/// `GOVERNANCE_QUORUM` voted.
+pub fn proposal_passed(yes_votes: u64, no_votes: u64) -> bool {
+    yes_votes > no_votes && yes_votes.saturating_add(no_votes) >= GOVERNANCE_QUORUM
+}
+
+/// Loyalty boost in basis points: 1.0x with no voting power, rising linearly to 1.5x
+/// once voting power covers the whole staked amount.
+pub fn ve_boost_bps(voting_power: u64, staked_amount: u64) -> u64 {
+    if staked_amount == 0 {
+        return 10_000;
+    }
+    let coverage = voting_power.min(staked_amount) as u128;
+    10_000 + ((5_000 * coverage) / staked_amount as u128) as u64
+}
+
+/// Rewards owed to a liquid pool holding `total_underlying` after `elapsed` seconds at `rate_bps` per year.
+pub fn liquid_accrual(total_underlying: u64, rate_bps: u16, elapsed: i64) -> u64 {
+    let elapsed = elapsed.max(0) as u128;
+    ((total_underlying as u128 * rate_bps as u128 * elapsed) / (10_000 * SECONDS_PER_YEAR as u128)) as u64
+}
+
+/// stMY shares minted for a deposit of `amount` into a pool holding `total_underlying`.
+/// Priced against `LIQUID_VIRTUAL_OFFSET` extra shares and backing, rounding down.
+pub fn liquid_shares_for_deposit(amount: u64, total_underlying: u64, total_shares: u64) -> u64 {
+    let offset = LIQUID_VIRTUAL_OFFSET as u128;
+    ((amount as u128 * (total_shares as u128 + offset)) / (total_underlying as u128 + offset)) as u64
+}
+
+/// multiYIELD redeemed for burning `shares` stMY, rounding down.
+pub fn liquid_underlying_for_shares(shares: u64, total_underlying: u64, total_shares: u64) -> u64 {
+    let offset = LIQUID_VIRTUAL_OFFSET as u128;
+    ((shares as u128 * (total_underlying as u128 + offset)) / (total_shares as u128 + offset)) as u64
+}
 
-        // On-chain DAO logic (assume check dao_approved = true)
-        require!(governance.dao_approved, CustomError::GovernanceNotApproved);
+/// Deserialize a program-owned account passed through `remaining_accounts`, or `None`
+/// when the caller supplied a placeholder for an account the user does not have.
+fn optional_program_account<'info, T: AccountSerialize + AccountDeserialize + Owner + Clone>(
+    info: &'info AccountInfo<'info>,
+) -> Result<Option<Account<'info, T>>> {
+    if info.owner != &crate::ID || info.data_is_empty() {
+        return Ok(None);
+    }
+    Ok(Some(Account::try_from(info)?))
+}
 
-        governance.reward_percentage = new_reward;
-        governance.lp_boost = new_lp_boost;
-        Ok(())
+/// Mint the liquid pool's accrued rewards into its vault and advance `last_accrual`.
+fn accrue_liquid_pool<'info>(
+    liquid_pool: &mut Account<'info, LiquidStakePool>,
+    liquid_vault: &mut InterfaceAccount<'info, TokenAccount>,
+    mint: &mut InterfaceAccount<'info, Mint>,
+    global_state: &mut Account<'info, GlobalState>,
+    reward_vault: &mut Option<InterfaceAccount<'info, TokenAccount>>,
+    token_program: &Interface<'info, TokenInterface>,
+    now: i64,
+) -> Result<()> {
+    // Clamp to what can still be emitted so a full cap or empty vault never blocks withdrawals
+    let reward = liquid_accrual(
+        liquid_vault.amount,
+        liquid_pool.reward_rate_bps,
+        now.saturating_sub(liquid_pool.last_accrual),
+    )
+    .min(emission_headroom(global_state, mint, reward_vault.as_ref()));
+    liquid_pool.last_accrual = now;
+    if reward == 0 {
+        return Ok(());
+    }
+
+    emit_multi_yield(
+        global_state,
+        mint,
+        reward_vault,
+        liquid_vault.to_account_info(),
+        token_program,
+        Emission::Staking,
+        reward,
+    )?;
+    liquid_vault.reload()
+}
+
+/// Pay `amount` of multiYIELD rewards to `to`. Every emission goes through here: in mint mode it
+/// mints as the global_state PDA under the max supply, and once a reward vault is configured it
+/// transfers from the vault instead so supply stays fixed. Either way it is recorded by category.
+fn emit_multi_yield<'info>(
+    global_state: &mut Account<'info, GlobalState>,
+    mint: &mut InterfaceAccount<'info, Mint>,
+    reward_vault: &mut Option<InterfaceAccount<'info, TokenAccount>>,
+    to: AccountInfo<'info>,
+    token_program: &Interface<'info, TokenInterface>,
+    category: Emission,
+    amount: u64,
+) -> Result<()> {
+    if amount == 0 {
+        return Ok(());
+    }
+    require!(mint.key() == global_state.mint, CustomError::InvalidMint);
+
+    let seeds = &[b"global_state".as_ref(), &[global_state.bump]];
+    let signer = &[&seeds[..]];
+    if global_state.reward_vault == Pubkey::default() {
+        check_supply_cap(mint.supply, amount, global_state.max_supply)?;
+        let cpi_accounts = MintTo {
+            mint: mint.to_account_info(),
+            to,
+            authority: global_state.to_account_info(),
+        };
+        let cpi_ctx = CpiContext::new_with_signer(token_program.to_account_info(), cpi_accounts, signer);
+        token_interface::mint_to(cpi_ctx, amount)?;
+        // Later mints in the same instruction must see the new supply
+        mint.reload()?;
+    } else {
+        let vault = reward_vault.as_mut().ok_or(CustomError::InvalidRewardVault)?;
+        require!(vault.amount >= amount, CustomError::RewardVaultDepleted);
+        let transfer_accounts = TransferChecked {
+            from: vault.to_account_info(),
+            mint: mint.to_account_info(),
+            to,
+            authority: global_state.to_account_info(),
+        };
+        let transfer_ctx = CpiContext::new_with_signer(token_program.to_account_info(), transfer_accounts, signer);
+        token_interface::transfer_checked(transfer_ctx, amount, mint.decimals)?;
+        vault.reload()?;
+        global_state.vault_distributed = global_state.vault_distributed.saturating_add(amount);
+    }
+
+    global_state.minted.record(category, amount);
+    Ok(())
+}
+
+/// How much of an `emit_multi_yield` of `amount` reaches its destination: vault payouts are
+/// transfers and lose any Token-2022 transfer fee on the way, minted rewards arrive whole.
+fn emitted_amount(global_state: &GlobalState, mint: &InterfaceAccount<Mint>, amount: u64) -> Result<u64> {
+    if global_state.reward_vault == Pubkey::default() {
+        return Ok(amount);
+    }
+    net_of_transfer_fee(&mint.to_account_info(), amount)
+}
+
+/// How much multiYIELD can still be emitted right now: supply-cap headroom in mint mode,
+/// or the reward vault balance in vault mode.
+fn emission_headroom(
+    global_state: &GlobalState,
+    mint: &InterfaceAccount<Mint>,
+    reward_vault: Option<&InterfaceAccount<TokenAccount>>,
+) -> u64 {
+    if global_state.reward_vault == Pubkey::default() {
+        global_state.max_supply.saturating_sub(mint.supply)
+    } else {
+        reward_vault.map_or(0, |vault| vault.amount)
+    }
+}
+
+/// `current` metadata as update data with only its URI replaced; Metaplex pads stored names
+/// and symbols with NULs, which must not be written back.
+pub fn metadata_with_uri(current: &mpl_token_metadata::accounts::Metadata, uri: String) -> DataV2 {
+    DataV2 {
+        name: current.name.trim_end_matches('\0').to_string(),
+        symbol: current.symbol.trim_end_matches('\0').to_string(),
+        uri,
+        seller_fee_basis_points: current.seller_fee_basis_points,
+        creators: current.creators.clone(),
+        collection: current.collection.clone(),
+        uses: current.uses.clone(),
+    }
+}
+
+/// Whether an account owned by `program` with `data` is an SPL token account whose owner is
+/// `authority`; such accounts must never reach a third-party CPI.
+pub fn is_token_account_owned_by(program: &Pubkey, data: &[u8], authority: &Pubkey) -> bool {
+    use anchor_lang::solana_program::program_pack::Pack;
+    (*program == anchor_spl::token::ID || *program == spl_token_2022::ID)
+        && data.len() >= spl_token_2022::state::Account::LEN
+        && data[32..64] == authority.to_bytes()
+}
+
+/// Least multiYIELD (base units) a buyback spending `spent` quote base units must return, given a
+/// Pyth price of `price * 10^expo` quote per multiYIELD and the allowed slippage.
+pub fn buyback_min_out(
+    spent: u64,
+    quote_decimals: u8,
+    my_decimals: u8,
+    price: u64,
+    expo: i32,
+    max_slippage_bps: u16,
+) -> Option<u64> {
+    if price == 0 {
+        return None;
+    }
+    // expected = spent * 10^(my_decimals - quote_decimals - expo) / price
+    let scale = my_decimals as i32 - quote_decimals as i32 - expo;
+    let pow = 10u128.checked_pow(scale.unsigned_abs())?;
+    let expected = if scale >= 0 {
+        (spent as u128).checked_mul(pow)? / price as u128
+    } else {
+        spent as u128 / (price as u128).checked_mul(pow)?
+    };
+    let min_out = expected * (10_000 - max_slippage_bps.min(10_000) as u128) / 10_000;
+    min_out.try_into().ok()
+}
+
+/// Seconds until a reward vault holding `balance` runs dry at the average payout rate
+/// (`distributed` over `elapsed` seconds). `None` until anything has been paid out.
+pub fn reward_runway_seconds(balance: u64, distributed: u64, elapsed: i64) -> Option<i64> {
+    if distributed == 0 || elapsed <= 0 {
+        return None;
+    }
+    let runway = balance as u128 * elapsed as u128 / distributed as u128;
+    Some(runway.min(i64::MAX as u128) as i64)
+}
+
+/// What a governance proposal authorizes: a gated instruction's name and its Borsh-encoded
+/// arguments, including the key of the account it changes.
+pub fn governance_action_hash(action: &str, args: &[u8]) -> [u8; 32] {
+    keccak::hashv(&[action.as_bytes(), args]).to_bytes()
+}
+
+/// Use up a passed proposal on the action it authorizes. Each proposal executes once, before
+/// its execution window closes.
+pub fn execute_proposal(proposal: &mut GovernanceProposal, action_hash: [u8; 32], now: i64) -> Result<()> {
+    require!(proposal.passed && !proposal.executed, CustomError::GovernanceNotApproved);
+    require!(proposal.action_hash == action_hash, CustomError::ProposalActionMismatch);
+    require!(
+        now < proposal.voting_ends_at.saturating_add(GOVERNANCE_EXECUTION_WINDOW),
+        CustomError::ProposalExpired
+    );
+    proposal.executed = true;
+    Ok(())
+}
+
+/// Protocol defaults shared by both `initialize` flavours.
+fn init_global_state(
+    global_state: &mut GlobalState,
+    governance: &mut Account<Governance>,
+    mint: Pubkey,
+    bump: u8,
+    authority: Pubkey,
+) {
+    global_state.mint = mint;
+    global_state.bump = bump;
+    global_state.authority = authority;
+    global_state.unstake_cooldown = 7 * 24 * 60 * 60; // default 7-day unbonding
+    global_state.max_nft_boost_bps = 5_000; // stacked NFT boosts cap at +50%
+    global_state.max_supply = MAX_SUPPLY;
+    global_state.governance = governance.key();
+
+    governance.reward_percentage = 10; // 10% base staking reward
+    governance.lp_boost = 0;
+}
+
+/// Grow a program-owned account to `new_len`, topping its rent up from `payer`.
+fn grow_account<'info>(
+    account: &AccountInfo<'info>,
+    new_len: usize,
+    payer: &Signer<'info>,
+    system_program: &Program<'info, System>,
+) -> Result<()> {
+    let shortfall = Rent::get()?.minimum_balance(new_len).saturating_sub(account.lamports());
+    if shortfall > 0 {
+        system_program::transfer(
+            CpiContext::new(
+                system_program.to_account_info(),
+                system_program::Transfer { from: payer.to_account_info(), to: account.clone() },
+            ),
+            shortfall,
+        )?;
+    }
+    account.realloc(new_len, true)?;
+    Ok(())
+}
+
+/// Close a program-owned account that has no typed `Account` wrapper, refunding its rent.
+fn close_program_account<'info>(account: &AccountInfo<'info>, destination: &AccountInfo<'info>) -> Result<()> {
+    let lamports = account.lamports();
+    **destination.try_borrow_mut_lamports()? = destination
+        .lamports()
+        .checked_add(lamports)
+        .ok_or(CustomError::ArithmeticOverflow)?;
+    **account.try_borrow_mut_lamports()? = 0;
+    account.assign(&system_program::ID);
+    account.realloc(0, false)?;
+    Ok(())
+}
+
+/// Owner and LP amount of an LP stake in the original program's layout.
+pub fn parse_legacy_lp_stake(data: &[u8]) -> Result<(Pubkey, u64)> {
+    require!(
+        data.len() == LEGACY_LP_STAKE_SPACE && data[..8] == LpStakeAccount::DISCRIMINATOR,
+        CustomError::NotLegacyAccount
+    );
+    let owner = Pubkey::try_from(&data[8..40]).map_err(|_| CustomError::NotLegacyAccount)?;
+    let lp_staked = u64::from_le_bytes(data[40..48].try_into().map_err(|_| CustomError::NotLegacyAccount)?);
+    Ok((owner, lp_staked))
+}
+
+/// Owner and escrowed NFT of an NFT stake from before NFT stacking. Only the single-NFT layout
+/// escrowed its NFT; its boost comes back expired.
+pub fn parse_legacy_nft_stake(data: &[u8]) -> Result<(Pubkey, Option<StakedNft>)> {
+    require!(
+        (data.len() == LEGACY_NFT_STAKE_SPACE || data.len() == SINGLE_NFT_STAKE_SPACE)
+            && data[..8] == NFTStakeAccount::DISCRIMINATOR,
+        CustomError::NotLegacyAccount
+    );
+    let pubkey_at = |offset: usize| {
+        Pubkey::try_from(&data[offset..offset + 32]).map_err(|_| CustomError::NotLegacyAccount)
+    };
+    let owner = pubkey_at(8)?;
+    let boosted = data[72] != 0;
+    if data.len() == LEGACY_NFT_STAKE_SPACE || !boosted {
+        return Ok((owner, None));
+    }
+    Ok((
+        owner,
+        Some(StakedNft {
+            mint: pubkey_at(40)?,
+            collection: pubkey_at(73)?,
+            tier: data[105],
+            boost_bps: u16::from_le_bytes([data[106], data[107]]),
+            boost_expires_at: 0,
+        }),
+    ))
+}
+
+/// Fail if minting `amount` on top of `supply` would exceed `max_supply`.
+pub fn check_supply_cap(supply: u64, amount: u64, max_supply: u64) -> Result<()> {
+    let new_supply = supply.checked_add(amount).ok_or(CustomError::ArithmeticOverflow)?;
+    require!(new_supply <= max_supply, CustomError::SupplyCapExceeded);
+    Ok(())
+}
+
+/// Amount that actually lands in the destination once any Token-2022 transfer fee on `mint` is withheld.
+pub fn net_of_transfer_fee(mint: &AccountInfo, amount: u64) -> Result<u64> {
+    if *mint.owner != spl_token_2022::ID {
+        return Ok(amount);
+    }
+    net_of_transfer_fee_at(&mint.try_borrow_data()?, amount, Clock::get()?.epoch)
+}
+
+/// `net_of_transfer_fee` for raw Token-2022 mint data at `epoch`.
+pub fn net_of_transfer_fee_at(mint_data: &[u8], amount: u64, epoch: u64) -> Result<u64> {
+    let state = StateWithExtensions::<spl_token_2022::state::Mint>::unpack(mint_data)?;
+    let fee = match state.get_extension::<transfer_fee::TransferFeeConfig>() {
+        Ok(config) => config
+            .calculate_epoch_fee(epoch, amount)
+            .ok_or(CustomError::ArithmeticOverflow)?,
+        Err(_) => 0,
+    };
+    Ok(amount - fee)
+}
+
+/// Growth of a fee vault's per-share accumulator when `amount` is shared across `total_staked`.
+pub fn fee_per_share_increment(amount: u64, total_staked: u64) -> u128 {
+    if total_staked == 0 {
+        return 0;
+    }
+    (amount as u128 * FEE_PRECISION) / total_staked as u128
+}
+
+/// Quote tokens `shares` would have earned from an accumulator of `acc` since it was zero.
+pub fn fee_reward_debt(shares: u64, acc: u128) -> u128 {
+    (shares as u128 * acc) / FEE_PRECISION
+}
+
+fn read_metadata_field<T: AnchorDeserialize>(buf: &mut &[u8]) -> Result<T> {
+    T::deserialize(buf).map_err(|_| error!(CustomError::InvalidNFTMetadata))
+}
+
+/// Advance the LP pool accumulator to `now`. Time with no stake in the pool emits nothing.
+fn update_lp_pool(lp_pool: &mut LpPool, lp_controller: &LpController, now: i64) {
+    let elapsed = now.saturating_sub(lp_pool.last_update).max(0) as u64;
+    let emitted = lp_pool_emission(
+        lp_controller.reward_per_second,
+        elapsed,
+        lp_pool.alloc_point,
+        lp_controller.total_alloc_point,
+    );
+    lp_pool.acc_reward_per_share = lp_pool
+        .acc_reward_per_share
+        .saturating_add(lp_acc_increment(emitted, lp_pool.total_weight));
+    lp_pool.last_update = now;
+}
+
+/// Update and persist every `LpPool` passed in `pools`, skipping `except`.
+fn update_lp_pools<'info>(
+    pools: &'info [AccountInfo<'info>],
+    except: Option<Pubkey>,
+    lp_controller: &LpController,
+    now: i64,
+) -> Result<()> {
+    for info in pools {
+        if Some(*info.key) == except {
+            continue;
+        }
+        let mut lp_pool: Account<LpPool> = Account::try_from(info)?;
+        update_lp_pool(&mut lp_pool, lp_controller, now);
+        lp_pool.exit(&crate::ID)?;
+    }
+    Ok(())
+}
+
+/// Whether `pools` names `pool_count` distinct accounts. Each is loaded as an `LpPool` when
+/// updated, so this means every pool is present exactly once.
+pub fn is_every_lp_pool(pools: &[Pubkey], pool_count: u32) -> bool {
+    let mut keys = pools.to_vec();
+    keys.sort();
+    keys.dedup();
+    keys.len() == pools.len() && pools.len() == pool_count as usize
+}
+
+/// Whether `mints` can be the gauge list: no mint repeats and each is one of `pool_mints`.
+pub fn is_valid_gauge_list(mints: &[Pubkey], pool_mints: &[Pubkey]) -> bool {
+    let mut distinct = mints.to_vec();
+    distinct.sort();
+    distinct.dedup();
+    distinct.len() == mints.len() && mints.iter().all(|mint| pool_mints.contains(mint))
+}
+
+/// A pool's share of `reward_per_second * elapsed`, by allocation points.
+pub fn lp_pool_emission(reward_per_second: u64, elapsed: u64, alloc_point: u64, total_alloc_point: u64) -> u64 {
+    if total_alloc_point == 0 {
+        return 0;
+    }
+    (reward_per_second as u128 * elapsed as u128 * alloc_point as u128 / total_alloc_point as u128) as u64
+}
+
+/// Split `power` votes across gauges by `weights_bps`.
+pub fn gauge_vote_split(power: u64, weights_bps: &[u16]) -> Vec<u64> {
+    weights_bps
+        .iter()
+        .map(|&bps| (power as u128 * bps as u128 / 10_000) as u64)
+        .collect()
+}
+
+/// Move everything `lp_stake` has earned up to `acc` into `pending_rewards`.
+fn settle_lp_rewards(lp_stake: &mut LpStakeAccount, acc: u128) -> Result<()> {
+    let earned = lp_reward_debt(lp_stake.weight(), acc).saturating_sub(lp_stake.reward_debt);
+    lp_stake.pending_rewards = lp_stake
+        .pending_rewards
+        .checked_add(earned.try_into().map_err(|_| CustomError::ArithmeticOverflow)?)
+        .ok_or(CustomError::ArithmeticOverflow)?;
+    lp_stake.reward_debt = lp_reward_debt(lp_stake.weight(), acc);
+    Ok(())
+}
+
+/// Re-tier `lp_stake` for `usd_value` and move the pool's total weight from `old_weight` to the
+/// stake's new weight. Rewards must already be settled at `lp_pool.acc_reward_per_share`.
+fn reweight_lp_stake(lp_stake: &mut LpStakeAccount, lp_pool: &mut LpPool, old_weight: u64, usd_value: u64) -> Result<()> {
+    lp_stake.reward_multiplier = lp_reward_multiplier(usd_value);
+    lp_pool.total_weight = lp_pool
+        .total_weight
+        .checked_sub(old_weight)
+        .and_then(|w| w.checked_add(lp_stake.weight()))
+        .ok_or(CustomError::ArithmeticOverflow)?;
+    lp_stake.reward_debt = lp_reward_debt(lp_stake.weight(), lp_pool.acc_reward_per_share);
+    Ok(())
+}
+
+/// Tiered LP multiplier based on the USD value (6 decimals) of the staker's LP position when it
+/// was last staked, unstaked or repriced.
+pub fn lp_reward_multiplier(usd_value: u64) -> u8 {
+    if usd_value > 1_000_000 * 1_000_000 {
+        5
+    } else if usd_value > 100_000 * 1_000_000 {
+        2
+    } else {
+        1
+    }
+}
+
+/// Load a Pyth price no older than `MAX_PRICE_AGE` and reject non-positive prices.
+fn fresh_pyth_price(feed: &AccountInfo, now: i64) -> Result<Price> {
+    let price_feed = load_price_feed_from_account_info(feed).map_err(|_| CustomError::OracleError)?;
+    let price = price_feed
+        .get_price_no_older_than(now, MAX_PRICE_AGE)
+        .ok_or(CustomError::OracleError)?;
+    require!(price.price > 0, CustomError::NegativePythPrice);
+    Ok(price)
+}
+
+/// Fresh `(collection, floor_scale_bps)` pairs from `[collection_config, floor_feed]` account pairs.
+fn nft_floor_scales<'info>(accounts: &'info [AccountInfo<'info>], now: i64) -> Result<Vec<(Pubkey, u64)>> {
+    require!(accounts.chunks_exact(2).remainder().is_empty(), CustomError::InvalidRemainingAccounts);
+    accounts
+        .chunks_exact(2)
+        .map(|pair| {
+            let collection_config: Account<CollectionConfig> = Account::try_from(&pair[0])?;
+            require_keys_eq!(pair[1].key(), collection_config.floor_price_feed, CustomError::OracleError);
+            let floor_feed = load_price_feed_from_account_info(&pair[1]).map_err(|_| CustomError::OracleError)?;
+            let floor_price = floor_feed
+                .get_price_no_older_than(now, MAX_PRICE_AGE)
+                .ok_or(CustomError::OracleError)?;
+            Ok((
+                collection_config.collection_mint,
+                floor_scale_bps(floor_price.price, collection_config.min_floor_price),
+            ))
+        })
+        .collect()
+}
+
+/// USD value (6 decimals) of `lp_amount` LP tokens, using fair LP pricing over the pool's
+/// reserves so a skewed reserve ratio cannot inflate it.
+#[allow(clippy::too_many_arguments)]
+fn lp_usd_value(
+    lp_pool: &LpPool,
+    lp_amount: u64,
+    lp_supply: u64,
+    reserve_a: &InterfaceAccount<TokenAccount>,
+    reserve_b: &InterfaceAccount<TokenAccount>,
+    price_feed_a: &AccountInfo,
+    price_feed_b: &AccountInfo,
+    now: i64,
+) -> Result<u64> {
+    let price_a = fresh_pyth_price(price_feed_a, now)?;
+    let price_b = fresh_pyth_price(price_feed_b, now)?;
+    let value_a = usd_value(reserve_a.amount, lp_pool.decimals_a, price_a.price as u64, price_a.expo)
+        .ok_or(CustomError::ArithmeticOverflow)?;
+    let value_b = usd_value(reserve_b.amount, lp_pool.decimals_b, price_b.price as u64, price_b.expo)
+        .ok_or(CustomError::ArithmeticOverflow)?;
+    let pool_value = fair_lp_pool_value(value_a, value_b).ok_or(CustomError::ArithmeticOverflow)?;
+    if lp_supply == 0 {
+        return Ok(0);
+    }
+    Ok((pool_value * lp_amount as u128 / lp_supply as u128).min(u64::MAX as u128) as u64)
+}
+
+/// USD value (6 decimals) of `amount` base units of a token priced at `price * 10^expo`.
+pub fn usd_value(amount: u64, decimals: u8, price: u64, expo: i32) -> Option<u128> {
+    let scale = 6 + expo - decimals as i32;
+    let pow = 10u128.checked_pow(scale.unsigned_abs())?;
+    let raw = (amount as u128).checked_mul(price as u128)?;
+    Some(if scale >= 0 { raw.checked_mul(pow)? } else { raw / pow })
+}
+
+/// Fair value of a constant-product pool: `2 * sqrt(value_a * value_b)`. Equals the plain sum
+/// when the reserves are balanced at oracle prices and is lower when they are pushed off it.
+pub fn fair_lp_pool_value(value_a: u128, value_b: u128) -> Option<u128> {
+    isqrt(value_a.checked_mul(value_b)?).checked_mul(2)
+}
+
+fn isqrt(n: u128) -> u128 {
+    if n < 2 {
+        return n;
+    }
+    let mut x = n;
+    let mut y = n / 2 + 1;
+    while y < x {
+        x = y;
+        y = (x + n / x) / 2;
+    }
+    x
+}
+
+/// LP payout with governance's `lp_boost` (extra percent, up to +10%) on top.
+pub fn apply_lp_boost(reward: u64, lp_boost: u8) -> u64 {
+    reward.saturating_add(((reward as u128 * lp_boost as u128) / 100) as u64)
+}
+
+/// Growth of an LP pool's per-weight accumulator when `emitted` is shared across `total_weight`.
+pub fn lp_acc_increment(emitted: u64, total_weight: u64) -> u128 {
+    if total_weight == 0 {
+        return 0;
+    }
+    (emitted as u128 * LP_ACC_PRECISION) / total_weight as u128
+}
+
+/// Rewards a stake of `weight` would have earned since the accumulator started, in reward units.
+pub fn lp_reward_debt(weight: u64, acc: u128) -> u128 {
+    weight as u128 * acc / LP_ACC_PRECISION
+}
+
+/// Read the mint and the *verified* collection (if any) from a Metaplex `MetadataV1` account.
+/// Metadata written before the collection field existed simply has no collection.
+pub fn parse_metadata_collection(data: &[u8]) -> Result<(Pubkey, Option<Pubkey>)> {
+    let buf = &mut &data[..];
+    let key: u8 = read_metadata_field(buf)?;
+    require!(key == 4, CustomError::InvalidNFTMetadata); // Key::MetadataV1
+    let _update_authority: Pubkey = read_metadata_field(buf)?;
+    let mint: Pubkey = read_metadata_field(buf)?;
+    let _name: String = read_metadata_field(buf)?;
+    let _symbol: String = read_metadata_field(buf)?;
+    let _uri: String = read_metadata_field(buf)?;
+    let _seller_fee_basis_points: u16 = read_metadata_field(buf)?;
+    // creators: Option<Vec<{ address, verified, share }>>
+    let _creators: Option<Vec<(Pubkey, bool, u8)>> = read_metadata_field(buf)?;
+    let _primary_sale_happened: bool = read_metadata_field(buf)?;
+    let _is_mutable: bool = read_metadata_field(buf)?;
+
+    if buf.is_empty() {
+        return Ok((mint, None));
+    }
+    let _edition_nonce: Option<u8> = read_metadata_field(buf)?;
+    let _token_standard: Option<u8> = read_metadata_field(buf)?;
+    let collection: Option<(bool, Pubkey)> = read_metadata_field(buf)?;
+    Ok((mint, collection.and_then(|(verified, key)| verified.then_some(key))))
+}
+
+/// Merkle leaf attesting that `mint` belongs to rarity `tier`.
+pub fn nft_tier_leaf(mint: &Pubkey, tier: u8) -> [u8; 32] {
+    keccak::hashv(&[mint.as_ref(), &[tier]]).to_bytes()
+}
+
+/// Merkle leaf of an audited balance `owner` may reclaim from the original program, `kind`
+/// naming which snapshot it belongs to.
+pub fn legacy_balance_leaf(kind: &[u8], owner: &Pubkey, amount: u64) -> [u8; 32] {
+    keccak::hashv(&[kind, owner.as_ref(), &amount.to_le_bytes()]).to_bytes()
+}
+
+/// Verify a keccak merkle proof built with sorted sibling pairs.
+pub fn verify_merkle_proof(proof: &[[u8; 32]], root: [u8; 32], leaf: [u8; 32]) -> bool {
+    let computed = proof.iter().fold(leaf, |node, sibling| {
+        if node <= *sibling {
+            keccak::hashv(&[&node, sibling]).to_bytes()
+        } else {
+            keccak::hashv(&[sibling, &node]).to_bytes()
+        }
+    });
+    computed == root
+}
+
+/// Stack NFT boosts with diminishing returns: the strongest counts in full, the next
+/// at half, the next at a quarter, and so on, with the total capped at `max_bps`.
+pub fn stacked_nft_boost_bps(boosts: &[u16], max_bps: u16) -> u64 {
+    let mut sorted = boosts.to_vec();
+    sorted.sort_unstable_by(|a, b| b.cmp(a));
+    let total: u64 = sorted
+        .iter()
+        .enumerate()
+        .map(|(i, bps)| (*bps as u64) >> i.min(63))
+        .sum();
+    total.min(max_bps as u64)
+}
+
+/// When a boost started at `now` expires; a non-positive `duration` expires it immediately.
+pub fn boost_expiry(now: i64, duration: i64) -> i64 {
+    now.saturating_add(duration.max(0))
+}
+
+/// Whether the crank may compound `position` at `now`: it opted into auto-compounding and was
+/// last claimed or compounded at least `CRANK_MIN_INTERVAL` ago.
+pub fn crank_due(position: &StakePosition, now: i64) -> bool {
+    position.auto_compound && now >= position.last_claim_time.saturating_add(CRANK_MIN_INTERVAL)
+}
+
+/// Split one position's crank payout into `(compounded, treasury_fee, tip)`: the cranker's 1% tip
+/// comes out of the reward, and any early-exit penalty still goes to the treasury.
+pub fn crank_split(final_reward: u64, treasury_fee: u64) -> (u64, u64, u64) {
+    let tip = final_reward / 100;
+    (final_reward - tip, treasury_fee, tip)
+}
+
+/// Boost scale from a fresh floor price: full at or above `min_floor`, proportional below it,
+/// and suspended entirely once the floor has halved.
+pub fn floor_scale_bps(floor_price: i64, min_floor: i64) -> u64 {
+    if floor_price >= min_floor || min_floor <= 0 {
+        return 10_000;
+    }
+    if floor_price <= 0 || floor_price.saturating_mul(2) < min_floor {
+        return 0;
     }
+    ((floor_price as u128 * 10_000) / min_floor as u128) as u64
+}
+
+/// Staking reward accrued by `amount` over the `elapsed` seconds since its last claim, as
+/// `(reward, treasury_fee)`: `reward_percentage` per year, `loyalty_bps` boost, `nft_boost_bps`
+/// NFT boost, and a 10% treasury penalty while the stake is under 7 days old (`time_staked`).
+pub fn stake_reward(
+    amount: u64,
+    time_staked: i64,
+    elapsed: i64,
+    reward_percentage: u8,
+    loyalty_bps: u64,
+    nft_boost_bps: u64,
+) -> (u64, u64) {
+    // If <7 days, 10% penalty goes to treasury
+    let min_duration = 7 * 24 * 60 * 60;
+    let penalty_rate = if time_staked < min_duration { 10 } else { 0 };
+
+    // Governance-set annual base rate, accrued per second
+    let elapsed = elapsed.max(0) as u128;
+    let base_reward = ((amount as u128 * reward_percentage as u128 * elapsed)
+        / (100 * SECONDS_PER_YEAR as u128)) as u64;
+    let loyalty_reward = ((base_reward as u128 * loyalty_bps as u128) / 10_000) as u64;
+
+    // NFT boost
+    let mut final_reward = loyalty_reward;
+    final_reward += ((loyalty_reward as u128 * nft_boost_bps as u128) / 10_000) as u64;
+
+    // If penalty applies
+    let treasury_fee = (final_reward * penalty_rate) / 100;
+    final_reward = final_reward.saturating_sub(treasury_fee);
+    (final_reward, treasury_fee)
 }
 
 // -----------------------------------------------
-//                Accounts Contexts
+//                    Errors
 // -----------------------------------------------
-#[derive(Accounts)]
-pub struct Initialize<'info> {
-    #[account(
-        init,
-        payer = user,
-        seeds = [b"global_state"],
-        bump,
-        space = 8 + 32 + 8 + 1 // plus protocol_wide_volume (u64)
-    )]
-    pub global_state: Account<'info, GlobalState>,
+#[error_code]
+pub enum CustomError {
+    #[msg("Flash Loan Attack Detected!")]
+    FlashLoanDetected,
+    #[msg("Trade Price Out of Expected Range")]
+    InvalidTradePrice,
+    #[msg("Early unstake is not allowed")]
+    EarlyUnstakePenalty,
+    #[msg("Nothing to claim in vesting schedule")]
+    NothingToClaim,
+    #[msg("Invalid reward or LP boost parameters")]
+    InvalidRewardParameters,
+    #[msg("Oracle error occurred")]
+    OracleError,
+    #[msg("Pyth price feed is negative")]
+    NegativePythPrice,
+    #[msg("Failed converting i64 to u64")]
+    ConversionError,
+    #[msg("Overflow in checked arithmetic")]
+    ArithmeticOverflow,
+    #[msg("Insufficient unique traders for anti-flashbot")]
+    InsufficientUniqueTraders,
+    #[msg("NFT floor price too low to stake")]
+    NFTFloorTooLow,
+    #[msg("Governance not approved")]
+    GovernanceNotApproved,
+    #[msg("Lock duration out of range")]
+    InvalidLockDuration,
+    #[msg("Stake position is still locked")]
+    PositionLocked,
+    #[msg("Insufficient staked amount")]
+    InsufficientStake,
+    #[msg("Cannot merge a position into itself")]
+    SamePosition,
+    #[msg("Staking pool account is not owned by the protocol")]
+    InvalidStakingPool,
+    #[msg("veMY lock has expired")]
+    LockExpired,
+    #[msg("Unauthorized")]
+    Unauthorized,
+    #[msg("Unstake cooldown out of range")]
+    InvalidCooldown,
+    #[msg("Unstake cooldown has not elapsed")]
+    CooldownNotElapsed,
+    #[msg("Unexpected remaining accounts layout")]
+    InvalidRemainingAccounts,
+    #[msg("Nothing is staked to receive fees")]
+    NothingStaked,
+    #[msg("Mint is not an NFT (supply 1, decimals 0)")]
+    NotAnNFT,
+    #[msg("Maximum number of staked NFTs reached")]
+    NFTLimitReached,
+    #[msg("This NFT is not staked")]
+    NFTNotStaked,
+    #[msg("Invalid Metaplex metadata for NFT")]
+    InvalidNFTMetadata,
+    #[msg("NFT collection is not verified or not allowlisted")]
+    CollectionNotAllowed,
+    #[msg("NFT rarity tier is invalid or not attested")]
+    InvalidNFTTier,
+    #[msg("Token account is not the configured transfer-fee destination")]
+    InvalidFeeDestination,
+    #[msg("Mint would exceed the multiYIELD max supply")]
+    SupplyCapExceeded,
+    #[msg("Mint is not the multiYIELD mint")]
+    InvalidMint,
+    #[msg("Reward vault is missing, not configured or already set")]
+    InvalidRewardVault,
+    #[msg("Reward vault does not hold enough multiYIELD")]
+    RewardVaultDepleted,
+    #[msg("AMM program is not whitelisted for buybacks")]
+    AmmNotWhitelisted,
+    #[msg("Swap output is below the oracle-bounded minimum")]
+    SlippageExceeded,
+    #[msg("Too many gauges")]
+    TooManyGauges,
+    #[msg("Gauge weights must match the gauge list and total at most 100%")]
+    InvalidGaugeVote,
+    #[msg("Gauge epoch has not ended yet")]
+    EpochNotOver,
+    #[msg("Proposal voting has closed")]
+    VotingClosed,
+    #[msg("Proposal voting is still open")]
+    VotingOpen,
+    #[msg("Proposal has already been finalized")]
+    ProposalFinalized,
+    #[msg("Token account is not the configured DAO treasury")]
+    InvalidTreasury,
+    #[msg("Too many fee vaults")]
+    TooManyFeeVaults,
+    #[msg("Account is not in the original program's layout")]
+    NotLegacyAccount,
+    #[msg("Token account is not the legacy LP vault")]
+    InvalidLegacyLpVault,
+    #[msg("Proposal authorizes a different action")]
+    ProposalActionMismatch,
+    #[msg("Proposal execution window has closed")]
+    ProposalExpired,
+    #[msg("Claim accrued rewards before withdrawing the whole stake")]
+    UnclaimedRewards,
+    #[msg("Balance is not in the audited legacy snapshot")]
+    NotInLegacySnapshot,
+    #[msg("Gauge mints must be distinct mints of existing LP pools")]
+    InvalidGaugeMints,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn weighted_timestamp_first_deposit_starts_now() {
+        assert_eq!(weighted_stake_timestamp(0, 0, 500, 1_000).unwrap(), 1_000);
+    }
+
+    #[test]
+    fn weighted_timestamp_equal_top_up_lands_halfway() {
+        assert_eq!(weighted_stake_timestamp(100, 1_000, 100, 3_000).unwrap(), 2_000);
+    }
+
+    #[test]
+    fn weighted_timestamp_small_top_up_keeps_loyalty() {
+        // 1% top-up 100 days later only moves the entry time by ~1 day
+        let day = 24 * 60 * 60;
+        let ts = weighted_stake_timestamp(1_000_000, 0, 10_000, 100 * day).unwrap();
+        assert!(ts < 2 * day);
+    }
+
+    #[test]
+    fn weighted_timestamp_tiny_seed_cannot_age_large_deposit() {
+        // Seeding with 1 token long ago gives a large deposit almost no age
+        let now = 200 * 24 * 60 * 60;
+        let ts = weighted_stake_timestamp(1, 0, 1_000_000, now).unwrap();
+        assert!(now - ts < 60);
+    }
+
+    #[test]
+    fn weighted_timestamp_zero_top_up_is_unchanged() {
+        assert_eq!(weighted_stake_timestamp(5_000, 1_234, 0, 9_999).unwrap(), 1_234);
+    }
+
+    #[test]
+    fn stake_rewards_accrue_with_time_since_last_claim() {
+        let year = SECONDS_PER_YEAR;
+        // Half a year at 10% pays 5%; claiming again right away pays nothing
+        assert_eq!(stake_reward(1_000_000, year, year / 2, 10, 10_000, 0), (50_000, 0));
+        assert_eq!(stake_reward(1_000_000, year, 0, 10, 10_000, 0), (0, 0));
+        // Repeated claims add up to the same as one claim over the whole period
+        let quarter = stake_reward(1_000_000, year, year / 4, 10, 10_000, 0).0;
+        assert_eq!(quarter * 4, stake_reward(1_000_000, year, year, 10, 10_000, 0).0);
+        // A mid-year top-up re-weights the last claim: the new tokens only earn from when they arrived
+        let last_claim = weighted_stake_timestamp(1_000, 0, 1_000, year / 2).unwrap();
+        assert_eq!(stake_reward(2_000, year, year - last_claim, 10, 10_000, 0), (150, 0));
+    }
+
+    #[test]
+    fn withdrawals_keep_rewards_accrued_so_far() {
+        let (rate, day) = (10, 24 * 60 * 60);
+        let now = 400 * day;
+        // Staked for a year, then half is withdrawn without claiming
+        let accrued = stake_reward(1_000_000, now, SECONDS_PER_YEAR, rate, 10_000, 0).0;
+        let last_claim = claim_time_after_withdrawal(1_000_000, now - SECONDS_PER_YEAR, 500_000, now).unwrap();
+        assert_eq!(stake_reward(500_000, now, now - last_claim, rate, 10_000, 0).0, accrued);
+        // Nothing accrued or nothing withdrawn leaves the claim time alone
+        assert_eq!(claim_time_after_withdrawal(1_000, now, 500, now).unwrap(), now);
+        assert_eq!(claim_time_after_withdrawal(1_000, now - day, 0, now).unwrap(), now - day);
+        // A full exit must claim first, unless it already has
+        assert!(claim_time_after_withdrawal(1_000, now - day, 1_000, now).is_err());
+        assert_eq!(claim_time_after_withdrawal(1_000, now, 1_000, now).unwrap(), now);
+        assert!(claim_time_after_withdrawal(1_000, now - day, 1_001, now).is_err());
+    }
+
+    #[test]
+    fn only_owner_or_delegate_may_claim() {
+        let owner = Pubkey::new_unique();
+        let delegate = Pubkey::new_unique();
+        let mut staker = StakeAccount {
+            owner,
+            amount: 1_000,
+            stake_timestamp: 0,
+            last_claim_time: 0,
+            delegate: Pubkey::default(),
+        };
+        assert!(staker.can_claim(&owner));
+        assert!(!staker.can_claim(&delegate));
+        assert!(!staker.can_claim(&Pubkey::default()));
+
+        staker.delegate = delegate;
+        assert!(staker.can_claim(&owner));
+        assert!(staker.can_claim(&delegate));
+        assert!(!staker.can_claim(&Pubkey::new_unique()));
+
+        // Revoking resets to the default key, which must not become a wildcard
+        staker.delegate = Pubkey::default();
+        assert!(!staker.can_claim(&delegate));
+        assert!(!staker.can_claim(&Pubkey::default()));
+    }
+
+    fn position(amount: u64, stake_timestamp: i64, last_claim_time: i64, auto_compound: bool) -> StakePosition {
+        StakePosition {
+            owner: Pubkey::new_unique(),
+            position_id: 0,
+            amount,
+            stake_timestamp,
+            lock_until: 0,
+            auto_compound,
+            nft_stake: Pubkey::default(),
+            last_claim_time,
+        }
+    }
+
+    #[test]
+    fn crank_waits_a_day_between_compounds() {
+        let day = CRANK_MIN_INTERVAL;
+        assert!(!crank_due(&position(1_000, 0, 10 * day, true), 11 * day - 1));
+        assert!(crank_due(&position(1_000, 0, 10 * day, true), 11 * day));
+        // Positions that did not opt in are never cranked
+        assert!(!crank_due(&position(1_000, 0, 0, false), 100 * day));
+    }
+
+    #[test]
+    fn crank_routes_penalty_to_treasury_and_tip_to_cranker() {
+        let day = CRANK_MIN_INTERVAL;
+        let mature = position(10_000_000, 0, 30 * day, true);
+        let young = position(10_000_000, 30 * day, 30 * day, true);
+        let now = 32 * day;
+        let reward = |p: &StakePosition| {
+            stake_reward(p.amount, now - p.stake_timestamp, now - p.last_claim_time, 10, 10_000, 0)
+        };
+
+        // Mature position: no penalty, 1% tip, the rest compounds
+        let (final_reward, treasury_fee) = reward(&mature);
+        assert_eq!(treasury_fee, 0);
+        let (compounded, fee, tip) = crank_split(final_reward, treasury_fee);
+        assert_eq!((compounded + tip, fee, tip), (final_reward, 0, final_reward / 100));
+
+        // Under 7 days old: 10% of the accrued reward goes to the treasury, not the pool
+        let (final_reward, treasury_fee) = reward(&young);
+        assert_eq!(treasury_fee, (final_reward + treasury_fee) / 10);
+        let (compounded, fee, tip) = crank_split(final_reward, treasury_fee);
+        assert_eq!(compounded + fee + tip, final_reward + treasury_fee);
+
+        // Two days of accrual pays twice one day's
+        assert_eq!(stake_reward(365_000_000, 30 * day, day, 10, 10_000, 0).0, 100_000);
+        assert_eq!(stake_reward(365_000_000, 30 * day, 2 * day, 10, 10_000, 0).0, 200_000);
+    }
+
+    #[test]
+    fn ve_power_decays_linearly_to_zero() {
+        assert_eq!(ve_voting_power(1_000, VE_MAX_LOCK, 0), 1_000);
+        assert_eq!(ve_voting_power(1_000, VE_MAX_LOCK, VE_MAX_LOCK / 2), 500);
+        assert_eq!(ve_voting_power(1_000, VE_MAX_LOCK, VE_MAX_LOCK), 0);
+        assert_eq!(ve_voting_power(1_000, 100, 200), 0);
+    }
+
+    #[test]
+    fn ve_boost_is_continuous_and_capped() {
+        assert_eq!(ve_boost_bps(0, 1_000), 10_000);
+        assert_eq!(ve_boost_bps(500, 1_000), 12_500);
+        assert_eq!(ve_boost_bps(1_000, 1_000), 15_000);
+        assert_eq!(ve_boost_bps(5_000, 1_000), 15_000);
+    }
+
+    fn global_state_data(edit: impl FnOnce(&mut GlobalState)) -> Vec<u8> {
+        let mut data = GlobalState::DISCRIMINATOR.to_vec();
+        data.resize(GLOBAL_STATE_SPACE, 0);
+        let mut global_state = GlobalState::try_deserialize(&mut &data[..]).unwrap();
+        edit(&mut global_state);
+        data.clear();
+        global_state.try_serialize(&mut data).unwrap();
+        data
+    }
+
+    fn token_account_data(mint: Pubkey, owner: Pubkey, amount: u64) -> Vec<u8> {
+        use anchor_lang::solana_program::program_pack::Pack;
+        let account = spl_token_2022::state::Account {
+            mint,
+            owner,
+            amount,
+            state: spl_token_2022::state::AccountState::Initialized,
+            ..Default::default()
+        };
+        let mut data = vec![0u8; spl_token_2022::state::Account::LEN];
+        account.pack_into_slice(&mut data);
+        data
+    }
+
+    #[test]
+    fn stake_withdrawals_only_draw_on_the_staking_pool() {
+        use anchor_lang::solana_program::program_pack::Pack;
+        use std::collections::BTreeSet;
+
+        let program_id = crate::ID;
+        let token_program = spl_token_2022::ID;
+        let system = anchor_lang::system_program::ID;
+        let mint = Pubkey::new_unique();
+        let staker = Pubkey::new_unique();
+        let (global_state_key, global_state_bump) = Pubkey::find_program_address(&[b"global_state"], &program_id);
+        let (ticket_key, _) =
+            Pubkey::find_program_address(&[b"unstake_ticket", staker.as_ref(), &0u64.to_le_bytes()], &program_id);
+        let (pool_key, _) = Pubkey::find_program_address(&[b"staking_pool"], &program_id);
+        let (liquid_vault_key, _) = Pubkey::find_program_address(&[b"liquid_vault"], &program_id);
+        let staker_token_key = Pubkey::new_unique();
+
+        let mut ticket_data = Vec::new();
+        UnstakeTicket { owner: staker, ticket_id: 0, amount: 100, requested_at: 0, claimable_at: 0 }
+            .try_serialize(&mut ticket_data)
+            .unwrap();
+        let mut global_state_data = global_state_data(|global_state| {
+            global_state.mint = mint;
+            global_state.bump = global_state_bump;
+        });
+        let mut mint_data = vec![0u8; spl_token_2022::state::Mint::LEN];
+        spl_token_2022::state::Mint { decimals: 6, is_initialized: true, ..Default::default() }
+            .pack_into_slice(&mut mint_data);
+        // The stMY vault is a multiYIELD account owned by global_state, just like the pool
+        let mut pool_data = token_account_data(mint, global_state_key, 1_000);
+        let mut liquid_vault_data = token_account_data(mint, global_state_key, 1_000);
+        let mut staker_token_data = token_account_data(mint, staker, 0);
+        let (mut staker_data, mut program_data) = (vec![], vec![]);
+        let (mut ticket_lamports, mut staker_token_lamports, mut pool_lamports, mut liquid_vault_lamports) = (1, 1, 1, 1);
+        let (mut global_state_lamports, mut staker_lamports, mut mint_lamports, mut program_lamports) = (1, 1, 1, 1);
+
+        let ticket = AccountInfo::new(&ticket_key, false, true, &mut ticket_lamports, &mut ticket_data, &program_id, false, 0);
+        let staker_token = AccountInfo::new(
+            &staker_token_key, false, true, &mut staker_token_lamports, &mut staker_token_data, &token_program, false, 0,
+        );
+        let pool = AccountInfo::new(&pool_key, false, true, &mut pool_lamports, &mut pool_data, &token_program, false, 0);
+        let liquid_vault = AccountInfo::new(
+            &liquid_vault_key, false, true, &mut liquid_vault_lamports, &mut liquid_vault_data, &token_program, false, 0,
+        );
+        let global_state = AccountInfo::new(
+            &global_state_key, false, true, &mut global_state_lamports, &mut global_state_data, &program_id, false, 0,
+        );
+        let staker_info = AccountInfo::new(&staker, true, true, &mut staker_lamports, &mut staker_data, &system, false, 0);
+        let mint_info = AccountInfo::new(&mint, false, false, &mut mint_lamports, &mut mint_data, &token_program, false, 0);
+        let token_program_info =
+            AccountInfo::new(&token_program, false, false, &mut program_lamports, &mut program_data, &system, true, 0);
+
+        fn load<'info>(mut accounts: &'info [AccountInfo<'info>]) -> Result<Pubkey> {
+            ClaimUnstake::try_accounts(&crate::ID, &mut accounts, &[], &mut Default::default(), &mut BTreeSet::new())
+                .map(|ctx| ctx.staking_pool_token_account.key())
+        }
+        let from_pool = [
+            ticket.clone(),
+            staker_token.clone(),
+            pool,
+            global_state.clone(),
+            staker_info.clone(),
+            mint_info.clone(),
+            token_program_info.clone(),
+        ];
+        assert_eq!(load(&from_pool).unwrap(), pool_key);
+        let from_liquid_vault =
+            [ticket, staker_token, liquid_vault, global_state, staker_info, mint_info, token_program_info];
+        assert!(load(&from_liquid_vault).is_err());
+    }
+
+    #[test]
+    fn governance_changes_need_the_admin_and_the_canonical_governance_pda() {
+        use std::collections::BTreeSet;
+
+        let admin = Pubkey::new_unique();
+        let (global_state_key, global_state_bump) = Pubkey::find_program_address(&[b"global_state"], &crate::ID);
+        let (governance_key, _) = Pubkey::find_program_address(&[b"governance"], &crate::ID);
+        let stray_governance_key = Pubkey::new_unique();
+
+        let mut governance_data = Vec::new();
+        Governance { reward_percentage: 10, lp_boost: 0 }.try_serialize(&mut governance_data).unwrap();
+        let mut stray_governance_data = governance_data.clone();
+        let (proposal_key, _) = Pubkey::find_program_address(&[b"proposal", &7u64.to_le_bytes()], &crate::ID);
+        let mut proposal_data = Vec::new();
+        passed_proposal(governance_action_hash("update_reward_parameters", &[20, 5]))
+            .try_serialize(&mut proposal_data)
+            .unwrap();
+
+        let mut global_state_data = global_state_data(|global_state| {
+            global_state.bump = global_state_bump;
+            global_state.authority = admin;
+            global_state.governance = governance_key;
+        });
+
+        let (mut lamports, mut governance_lamports, mut stray_lamports, mut signer_lamports) = (1, 1, 1, 0);
+        let mut proposal_lamports = 1;
+        let program_id = crate::ID;
+        let system = anchor_lang::system_program::ID;
+        let global_state_info = AccountInfo::new(
+            &global_state_key, false, false, &mut lamports, &mut global_state_data, &program_id, false, 0,
+        );
+        let governance_info = AccountInfo::new(
+            &governance_key, false, true, &mut governance_lamports, &mut governance_data, &program_id, false, 0,
+        );
+        let stray_governance_info = AccountInfo::new(
+            &stray_governance_key, false, true, &mut stray_lamports, &mut stray_governance_data, &program_id, false, 0,
+        );
+        let proposal_info = AccountInfo::new(
+            &proposal_key, false, true, &mut proposal_lamports, &mut proposal_data, &program_id, false, 0,
+        );
+        let outsider = Pubkey::new_unique();
+        let (mut admin_data, mut outsider_data, mut outsider_lamports) = (vec![], vec![], 0);
+        let admin_info = AccountInfo::new(&admin, true, false, &mut signer_lamports, &mut admin_data, &system, false, 0);
+        let outsider_info =
+            AccountInfo::new(&outsider, true, false, &mut outsider_lamports, &mut outsider_data, &system, false, 0);
+
+        fn load<'info>(mut accounts: &'info [AccountInfo<'info>]) -> Result<Pubkey> {
+            UpdateGovernance::try_accounts(&crate::ID, &mut accounts, &[], &mut Default::default(), &mut BTreeSet::new())
+                .map(|ctx| ctx.governance.key())
+        }
+        let admin_signed =
+            [global_state_info.clone(), governance_info.clone(), proposal_info.clone(), admin_info.clone()];
+        assert_eq!(load(&admin_signed).unwrap(), governance_key);
+        // Any other signer is rejected even with a passed proposal
+        let outsider_signed = [global_state_info.clone(), governance_info, proposal_info.clone(), outsider_info];
+        assert!(load(&outsider_signed).is_err());
+        // So is a governance-shaped account that is not the canonical PDA
+        let stray_governance = [global_state_info, stray_governance_info, proposal_info, admin_info];
+        assert!(load(&stray_governance).is_err());
+    }
+
+    fn passed_proposal(action_hash: [u8; 32]) -> GovernanceProposal {
+        GovernanceProposal {
+            proposal_id: 7,
+            proposer: Pubkey::new_unique(),
+            yes_votes: GOVERNANCE_QUORUM,
+            no_votes: 0,
+            voting_ends_at: 1_000,
+            finalized: true,
+            passed: true,
+            action_hash,
+            executed: false,
+        }
+    }
+
+    #[test]
+    fn proposals_execute_only_their_own_action_once() {
+        let action = governance_action_hash("update_reward_parameters", &(20u8, 5u8).try_to_vec().unwrap());
+        let other = governance_action_hash("update_reward_parameters", &(50u8, 5u8).try_to_vec().unwrap());
+        assert_ne!(action, governance_action_hash("set_gauge_mints", &(20u8, 5u8).try_to_vec().unwrap()));
+
+        let mut proposal = passed_proposal(action);
+        assert!(execute_proposal(&mut proposal, other, 1_000).is_err());
+        execute_proposal(&mut proposal, action, 1_000).unwrap();
+        assert!(execute_proposal(&mut proposal, action, 1_000).is_err());
+
+        // Expired or failed proposals authorize nothing
+        let mut stale = passed_proposal(action);
+        assert!(execute_proposal(&mut stale, action, 1_000 + GOVERNANCE_EXECUTION_WINDOW).is_err());
+        let mut failed = GovernanceProposal { passed: false, ..passed_proposal(action) };
+        assert!(execute_proposal(&mut failed, action, 1_000).is_err());
+    }
+
+    #[test]
+    fn proposals_pass_on_strict_majority() {
+        let half = GOVERNANCE_QUORUM / 2;
+        assert!(proposal_passed(half + 1, half - 1));
+        assert!(!proposal_passed(half, half));
+        assert!(!proposal_passed(0, 0));
+        assert!(!proposal_passed(10, GOVERNANCE_QUORUM));
+        // A lone voter below quorum cannot pass anything
+        assert!(!proposal_passed(GOVERNANCE_QUORUM - 1, 0));
+        assert!(proposal_passed(GOVERNANCE_QUORUM, 0));
+    }
+
+    #[test]
+    fn liquid_exchange_rate_grows_with_accrual() {
+        let unit = LIQUID_VIRTUAL_OFFSET;
+        // First depositor gets 1:1 shares
+        assert_eq!(liquid_shares_for_deposit(1_000 * unit, 0, 0), 1_000 * unit);
+        // A year at 10% grows the vault by 10%
+        assert_eq!(liquid_accrual(1_000 * unit, 1_000, SECONDS_PER_YEAR), 100 * unit);
+        // Later depositors get fewer shares, earlier holders redeem more
+        // (the virtual offset moves the rate by about one part in the pool size)
+        let shares = liquid_shares_for_deposit(1_100 * unit, 1_100 * unit, 1_000 * unit);
+        assert!((1_000 * unit..=1_001 * unit).contains(&shares));
+        let redeemed = liquid_underlying_for_shares(1_000 * unit, 1_100 * unit, 1_000 * unit);
+        assert!((1_099 * unit..=1_100 * unit).contains(&redeemed));
+    }
+
+    #[test]
+    fn liquid_shares_round_down_in_the_pools_favor() {
+        // Deposit then redeem never returns more than was put in
+        for (amount, underlying, shares) in [(7, 1_000_003, 999_999), (1, 10, 3), (12_345, 99_999_999, 33_333_333)] {
+            let minted = liquid_shares_for_deposit(amount, underlying, shares);
+            assert!(liquid_underlying_for_shares(minted, underlying + amount, shares + minted) <= amount);
+        }
+        // Dust deposits into a grown pool mint nothing rather than rounding up
+        assert_eq!(liquid_shares_for_deposit(1, 3_000_000, 1_000_000), 0);
+        assert_eq!(liquid_underlying_for_shares(0, 1_000, 1_000), 0);
+    }
+
+    #[test]
+    fn liquid_donation_cannot_zero_out_later_deposits() {
+        // Attacker deposits 1 base unit, then donates 10k tokens straight into the vault
+        let attacker_shares = liquid_shares_for_deposit(1, 0, 0);
+        let donated = 1 + 10_000 * LIQUID_VIRTUAL_OFFSET;
+        // A victim depositing 1k tokens still receives shares...
+        let victim_shares = liquid_shares_for_deposit(1_000 * LIQUID_VIRTUAL_OFFSET, donated, attacker_shares);
+        assert!(victim_shares > 0);
+        // ...and the attacker redeems far less than they donated
+        let total_underlying = donated + 1_000 * LIQUID_VIRTUAL_OFFSET;
+        let total_shares = attacker_shares + victim_shares;
+        assert!(liquid_underlying_for_shares(attacker_shares, total_underlying, total_shares) < donated / 1_000);
+    }
+
+    #[test]
+    fn fee_share_is_pro_rata() {
+        // 1_000 USDC over 4_000 staked: a 1_000 stake is owed a quarter
+        let acc = fee_per_share_increment(1_000, 4_000);
+        assert_eq!(fee_reward_debt(1_000, acc), 250);
+        assert_eq!(fee_reward_debt(3_000, acc), 750);
+        assert_eq!(fee_per_share_increment(1_000, 0), 0);
+    }
 
-    #[account(
-        init,
-        payer = user,
-        mint::decimals = 6,
-        mint::authority = global_state,
-    )]
-    pub mint: Account<'info, Mint>,
+    fn fee_claim() -> FeeClaim {
+        FeeClaim {
+            owner: Pubkey::new_unique(),
+            holder: Pubkey::new_unique(),
+            shares: 0,
+            reward_debt: [0; MAX_FEE_VAULTS],
+            pending: [0; MAX_FEE_VAULTS],
+        }
+    }
 
-    #[account(mut)]
-    pub user: Signer<'info>,
+    #[test]
+    fn fee_top_up_before_claim_earns_no_extra_share() {
+        let mut acc = [0u128; MAX_FEE_VAULTS];
+        let mut total_staked = 0;
+        let (mut alice, mut bob) = (fee_claim(), fee_claim());
+        alice.settle(&acc, &mut total_staked, 1_000).unwrap();
+        bob.settle(&acc, &mut total_staked, 3_000).unwrap();
+        assert_eq!(total_staked, 4_000);
 
-    pub system_program: Program<'info, System>,
-    pub token_program: Program<'info, Token>,
-    pub rent: Sysvar<'info, Rent>,
-}
+        acc[0] += fee_per_share_increment(1_000, total_staked);
 
-#[derive(Accounts)]
-pub struct RewardTrade<'info> {
-    #[account(mut, seeds = [b"global_state"], bump = global_state.bump)]
-    pub global_state: Account<'info, GlobalState>,
+        // Alice tops up massively right before claiming; the deposit was made on 1_000
+        alice.settle(&acc, &mut total_staked, 1_001_000).unwrap();
+        alice.settle(&acc, &mut total_staked, 1_001_000).unwrap();
+        bob.settle(&acc, &mut total_staked, 3_000).unwrap();
+        assert_eq!(alice.pending[0], 250);
+        assert_eq!(bob.pending[0], 750);
+        assert_eq!(total_staked, 1_004_000);
 
-    // for dynamic protocol volume adjustments
-    // (publicly stored in global_state -> protocol_wide_volume)
-    // which can increment on each trade if desired.
+        // Leaving settles what was earned and takes the shares out of the denominator
+        acc[0] += fee_per_share_increment(1_004, total_staked);
+        alice.settle(&acc, &mut total_staked, 0).unwrap();
+        assert_eq!(alice.pending[0], 250 + 1_001);
+        assert_eq!(total_staked, 3_000);
+    }
 
+    // edition_nonce, token_standard, collection
+    type MetadataTail = (Option<u8>, Option<u8>, Option<(bool, Pubkey)>);
 
+    fn metadata_bytes(mint: Pubkey, tail: Option<MetadataTail>) -> Vec<u8> {
+        let mut data = vec![4u8];
+        data.extend(Pubkey::new_unique().to_bytes());
+        data.extend(mint.to_bytes());
+        for field in ["Yield Cat #1", "YCAT", "https://example.com/1.json"] {
+            field.to_string().serialize(&mut data).unwrap();
+        }
+        500u16.serialize(&mut data).unwrap();
+        Some(vec![(Pubkey::new_unique(), true, 100u8)]).serialize(&mut data).unwrap();
+        data.extend([1u8, 1u8]);
+        if let Some(tail) = tail {
+            tail.serialize(&mut data).unwrap();
+        }
+        // Metadata accounts are zero-padded to a fixed size
+        data.resize(679, 0);
+        data
+    }
 
-    #[account(mut)]
-    pub mint: Account<'info, Mint>,
+    #[test]
+    fn metadata_parser_returns_verified_collection() {
+        let mint = Pubkey::new_unique();
+        let collection = Pubkey::new_unique();
+        let data = metadata_bytes(mint, Some((Some(255), Some(0), Some((true, collection)))));
+        assert_eq!(parse_metadata_collection(&data).unwrap(), (mint, Some(collection)));
+    }
 
-    #[account(mut)]
-    pub trader_token_account: Account<'info, TokenAccount>,
+    #[test]
+    fn metadata_parser_ignores_unverified_or_missing_collection() {
+        let mint = Pubkey::new_unique();
+        let unverified = metadata_bytes(mint, Some((None, None, Some((false, Pubkey::new_unique())))));
+        assert_eq!(parse_metadata_collection(&unverified).unwrap(), (mint, None));
+        let legacy = metadata_bytes(mint, None);
+        assert_eq!(parse_metadata_collection(&legacy).unwrap(), (mint, None));
+    }
 
-    #[account(mut)]
-    pub insurance_pool_account: Account<'info, TokenAccount>,
+    #[test]
+    fn metadata_uri_update_keeps_everything_else() {
+        let collection = Pubkey::new_unique();
+        let data = metadata_bytes(Pubkey::new_unique(), Some((Some(255), Some(0), Some((true, collection)))));
+        let mut current = mpl_token_metadata::accounts::Metadata::from_bytes(&data).unwrap();
+        current.name = format!("{:\0<32}", current.name);
+        current.symbol = format!("{:\0<10}", current.symbol);
 
-    #[account(
-        mut,
-        seeds = [b"volume", trader_token_account.owner.key().as_ref()],
-        bump
-    )]
-    pub trader_volume: Account<'info, TraderVolume>,
+        let updated = metadata_with_uri(&current, "https://example.com/2.json".to_string());
+        assert_eq!(updated.name, "Yield Cat #1");
+        assert_eq!(updated.symbol, "YCAT");
+        assert_eq!(updated.uri, "https://example.com/2.json");
+        assert_eq!(updated.seller_fee_basis_points, 500);
+        assert_eq!(updated.creators, current.creators);
+        assert_eq!(updated.collection.map(|c| (c.verified, c.key)), Some((true, collection)));
+        assert!(updated.uses.is_none());
+    }
 
-    /// Pyth price feed as a generic AccountInfo
-    #[account()]
-    pub pyth_price_feed: AccountInfo<'info>,
+    #[test]
+    fn metadata_parser_rejects_other_account_kinds() {
+        let mut data = metadata_bytes(Pubkey::new_unique(), None);
+        data[0] = 6; // MasterEditionV2
+        assert!(parse_metadata_collection(&data).is_err());
+        assert!(parse_metadata_collection(&[4u8; 10]).is_err());
+    }
 
-    pub token_program: Program<'info, Token>,
-}
+    fn merkle_parent(a: [u8; 32], b: [u8; 32]) -> [u8; 32] {
+        if a <= b {
+            keccak::hashv(&[&a, &b]).to_bytes()
+        } else {
+            keccak::hashv(&[&b, &a]).to_bytes()
+        }
+    }
 
-#[derive(Accounts)]
-pub struct StakeTokens<'info> {
-    #[account(
-        init_if_needed,
-        payer = staker_authority,
-        space = 8 + 32 + 8 + 8 + 1,
-        seeds = [b"stake", staker_authority.key().as_ref()],
-        bump
-    )]
-    pub staker: Account<'info, StakeAccount>,
+    #[test]
+    fn tier_merkle_proof_round_trip() {
+        let mints: Vec<Pubkey> = (0..4).map(|_| Pubkey::new_unique()).collect();
+        let leaves: Vec<[u8; 32]> = mints
+            .iter()
+            .enumerate()
+            .map(|(i, mint)| nft_tier_leaf(mint, i as u8 + 1))
+            .collect();
+        let left = merkle_parent(leaves[0], leaves[1]);
+        let right = merkle_parent(leaves[2], leaves[3]);
+        let root = merkle_parent(left, right);
 
-    #[account(mut)]
-    pub staker_token_account: Account<'info, TokenAccount>,
+        // mints[2] is tier 3
+        assert!(verify_merkle_proof(&[leaves[3], left], root, nft_tier_leaf(&mints[2], 3)));
+        // Claiming a better tier for the same mint fails
+        assert!(!verify_merkle_proof(&[leaves[3], left], root, nft_tier_leaf(&mints[2], 4)));
+        // An empty proof only works for a single-leaf tree
+        assert!(verify_merkle_proof(&[], leaves[0], leaves[0]));
+    }
 
-    #[account(mut)]
-    pub staking_pool_token_account: Account<'info, TokenAccount>,
+    #[test]
+    fn nft_boosts_stack_with_diminishing_returns() {
+        assert_eq!(stacked_nft_boost_bps(&[], 5_000), 0);
+        assert_eq!(stacked_nft_boost_bps(&[2_000], 5_000), 2_000);
+        // Order does not matter: 2000 + 1000/2 + 800/4
+        assert_eq!(stacked_nft_boost_bps(&[800, 2_000, 1_000], 5_000), 2_700);
+        // Many NFTs converge below twice the best boost and respect the cap
+        assert_eq!(stacked_nft_boost_bps(&[2_000; 8], 10_000), 3_983);
+        assert_eq!(stacked_nft_boost_bps(&[2_000; 8], 3_000), 3_000);
+    }
 
-    #[account(mut)]
-    pub staker_authority: Signer<'info>,
+    #[test]
+    fn floor_drop_scales_then_suspends_boost() {
+        assert_eq!(floor_scale_bps(1_500, 1_000), 10_000);
+        assert_eq!(floor_scale_bps(1_000, 1_000), 10_000);
+        assert_eq!(floor_scale_bps(750, 1_000), 7_500);
+        assert_eq!(floor_scale_bps(500, 1_000), 5_000);
+        assert_eq!(floor_scale_bps(499, 1_000), 0);
+        assert_eq!(floor_scale_bps(-5, 1_000), 0);
+    }
 
-    pub token_program: Program<'info, Token>,
-    pub system_program: Program<'info, System>,
-    pub rent: Sysvar<'info, Rent>,
-}
+    #[test]
+    fn nft_boosts_expire_and_follow_floor() {
+        let collection = Pubkey::new_unique();
+        let nft = |boost_bps, boost_expires_at| StakedNft {
+            mint: Pubkey::new_unique(),
+            collection,
+            tier: 0,
+            boost_bps,
+            boost_expires_at,
+        };
+        let nft_stake = NFTStakeAccount {
+            owner: Pubkey::new_unique(),
+            nfts: vec![nft(2_000, 200), nft(1_000, 100)],
+        };
+        let full_floor = [(collection, 10_000)];
+        assert_eq!(nft_stake.active_boost_bps(5_000, 50, &full_floor), 2_500);
+        // Second boost has expired
+        assert_eq!(nft_stake.active_boost_bps(5_000, 100, &full_floor), 2_000);
+        // Floor at 75% of threshold scales the remaining boost
+        assert_eq!(nft_stake.active_boost_bps(5_000, 100, &[(collection, 7_500)]), 1_500);
+        // Without a fresh floor check for its collection a boost counts for nothing
+        assert_eq!(nft_stake.active_boost_bps(5_000, 50, &[]), 0);
+        assert_eq!(nft_stake.active_boost_bps(5_000, 50, &[(Pubkey::new_unique(), 10_000)]), 0);
+        // A zero duration never grants a boost
+        assert_eq!(boost_expiry(10, 0), 10);
+        assert_eq!(boost_expiry(10, 5), 15);
+    }
 
-#[derive(Accounts)]
-pub struct ClaimStakeRewards<'info> {
-    #[account(mut, seeds = [b"stake", staker.owner.as_ref()], bump)]
-    pub staker: Account<'info, StakeAccount>,
+    #[test]
+    fn unstaking_an_escrowed_nft_drops_only_its_boost() {
+        let collection = Pubkey::new_unique();
+        let nft = |boost_bps| StakedNft {
+            mint: Pubkey::new_unique(),
+            collection,
+            tier: 0,
+            boost_bps,
+            boost_expires_at: 100,
+        };
+        let (kept, escrowed) = (nft(2_000), nft(1_000));
+        let mut nft_stake = NFTStakeAccount {
+            owner: Pubkey::new_unique(),
+            nfts: vec![kept.clone(), escrowed.clone()],
+        };
 
-    #[account(mut)]
-    pub staker_reward_account: Account<'info, TokenAccount>,
+        let removed = nft_stake.remove_nft(&escrowed.mint).unwrap();
+        assert_eq!(removed.mint, escrowed.mint);
+        assert_eq!(nft_stake.active_boost_bps(5_000, 0, &[(collection, 10_000)]), 2_000);
+        // The same NFT cannot be withdrawn twice, nor one that was never escrowed
+        assert!(nft_stake.remove_nft(&escrowed.mint).is_err());
+        assert!(nft_stake.remove_nft(&Pubkey::new_unique()).is_err());
+        assert_eq!(nft_stake.nfts.len(), 1);
 
-    #[account(
-        mut,
-        seeds = [b"global_state"],
-        bump = global_state.bump
-    )]
-    pub global_state: Account<'info, GlobalState>,
+        // Each NFT gets its own program-owned escrow address
+        let escrow = |mint: &Pubkey| Pubkey::find_program_address(&[b"nft_escrow", mint.as_ref()], &crate::ID).0;
+        assert_ne!(escrow(&kept.mint), escrow(&escrowed.mint));
+        assert!(!escrow(&kept.mint).is_on_curve());
+    }
 
-    #[account(mut)]
-    pub mint: Account<'info, Mint>,
+    fn token_2022_mint(extensions: &[ExtensionType], fee: Option<(u16, u64)>) -> Vec<u8> {
+        use anchor_spl::token_2022::spl_token_2022::extension::StateWithExtensionsMut;
+        let len = ExtensionType::try_calculate_account_len::<spl_token_2022::state::Mint>(extensions).unwrap();
+        let mut data = vec![0u8; len];
+        let mut state = StateWithExtensionsMut::<spl_token_2022::state::Mint>::unpack_uninitialized(&mut data).unwrap();
+        state.base.decimals = 6;
+        state.base.is_initialized = true;
+        state.pack_base();
+        if let Some((bps, maximum_fee)) = fee {
+            let config = state.init_extension::<transfer_fee::TransferFeeConfig>(true).unwrap();
+            let transfer_fee = transfer_fee::TransferFee {
+                epoch: 0.into(),
+                maximum_fee: maximum_fee.into(),
+                transfer_fee_basis_points: bps.into(),
+            };
+            config.older_transfer_fee = transfer_fee;
+            config.newer_transfer_fee = transfer_fee;
+        }
+        if extensions.contains(&ExtensionType::MetadataPointer) {
+            state.init_extension::<metadata_pointer::MetadataPointer>(true).unwrap();
+        }
+        state.init_account_type().unwrap();
+        data
+    }
 
-    /// The staked NFT account for reward boosts (if any).
-    #[account(mut)]
-    pub nft_stake: Account<'info, NFTStakeAccount>,
+    #[test]
+    fn transfer_fee_is_withheld_up_to_its_maximum() {
+        let both = [ExtensionType::TransferFeeConfig, ExtensionType::MetadataPointer];
+        // 1% fee, rounded up, capped at 50 tokens
+        let mint = token_2022_mint(&both, Some((100, 50)));
+        assert_eq!(net_of_transfer_fee_at(&mint, 1_000, 0).unwrap(), 990);
+        assert_eq!(net_of_transfer_fee_at(&mint, 1_001, 0).unwrap(), 990);
+        assert_eq!(net_of_transfer_fee_at(&mint, 1_000_000, 5).unwrap(), 1_000_000 - 50);
+        assert_eq!(net_of_transfer_fee_at(&mint, 0, 0).unwrap(), 0);
 
-    /// The DAO treasury (for penalty fees).
-    #[account(mut)]
-    pub dao_treasury_account: Account<'info, TokenAccount>,
+        // No fee extension, or a zero fee, leaves the amount untouched
+        let plain = token_2022_mint(&[ExtensionType::MetadataPointer], None);
+        assert_eq!(net_of_transfer_fee_at(&plain, 1_000, 0).unwrap(), 1_000);
+        let free = token_2022_mint(&both, Some((0, 50)));
+        assert_eq!(net_of_transfer_fee_at(&free, 1_000, 0).unwrap(), 1_000);
+    }
 
-    pub token_program: Program<'info, Token>,
-}
+    #[test]
+    fn per_position_compounds_never_exceed_the_batch_transfer() {
+        let both = [ExtensionType::TransferFeeConfig, ExtensionType::MetadataPointer];
+        for mint in [token_2022_mint(&both, Some((100, 50))), token_2022_mint(&both, Some((333, 4)))] {
+            for shares in [&[333u64, 333, 334][..], &[1, 1, 998], &[5_000, 7, 20_000]] {
+                let credited: u64 = shares.iter().map(|s| net_of_transfer_fee_at(&mint, *s, 0).unwrap()).sum();
+                let delivered = net_of_transfer_fee_at(&mint, shares.iter().sum(), 0).unwrap();
+                assert!(credited <= delivered);
+            }
+        }
+    }
 
-#[derive(Accounts)]
-pub struct StakeNFT<'info> {
-    #[account(
-        init_if_needed,
-        payer = user,
-        space = 8 + 32 + 32 + 1,
-        seeds = [b"nft_stake", user.key().as_ref()],
-        bump
-    )]
-    pub nft_stake: Account<'info, NFTStakeAccount>,
+    #[test]
+    fn supply_cap_allows_minting_up_to_max() {
+        assert!(check_supply_cap(900, 100, 1_000).is_ok());
+        assert!(check_supply_cap(900, 101, 1_000).is_err());
+        assert!(check_supply_cap(u64::MAX, 1, u64::MAX).is_err());
 
-    #[account(mut)]
-    pub user: Signer<'info>,
+        let mut minted = EmissionTotals::default();
+        minted.record(Emission::Staking, 40);
+        minted.record(Emission::Staking, 2);
+        minted.record(Emission::Lp, 7);
+        assert_eq!((minted.staking, minted.lp, minted.trading), (42, 7, 0));
+    }
 
-    #[account(mut)]
-    pub nft_mint: Account<'info, Mint>,
+    #[test]
+    fn reward_runway_uses_average_payout_rate() {
+        assert_eq!(reward_runway_seconds(1_000, 0, 100), None);
+        // 10 paid out per second since activation -> 1_000 lasts 100s more
+        assert_eq!(reward_runway_seconds(1_000, 1_000, 100), Some(100));
+        assert_eq!(reward_runway_seconds(0, 1_000, 100), Some(0));
+    }
 
-    /// Additional feed to check NFT floor price
-    #[account()]
-    pub nft_floor_price_feed: AccountInfo<'info>,
+    #[test]
+    fn lp_rewards_accrue_with_stake_and_time() {
+        let mut pool = LpPool {
+            lp_mint: Pubkey::new_unique(),
+            vault: Pubkey::new_unique(),
+            alloc_point: 1,
+            acc_reward_per_share: 0,
+            total_weight: 0,
+            last_update: 0,
+            reserve_a: Pubkey::default(),
+            reserve_b: Pubkey::default(),
+            price_feed_a: Pubkey::default(),
+            price_feed_b: Pubkey::default(),
+            decimals_a: 6,
+            decimals_b: 6,
+        };
+        let controller = LpController { reward_per_second: 10, total_alloc_point: 1, pool_count: 1 };
+        let mut a = LpStakeAccount {
+            owner: Pubkey::new_unique(),
+            lp_mint: pool.lp_mint,
+            lp_staked: 100,
+            reward_multiplier: 1,
+            reward_debt: 0,
+            pending_rewards: 0,
+            last_claim_time: 0,
+        };
+        pool.total_weight = a.weight();
 
-    pub system_program: Program<'info, System>,
-    pub rent: Sysvar<'info, Rent>,
-    // Could also add a token_program if additional steps are needed
-}
+        update_lp_pool(&mut pool, &controller, 100);
+        settle_lp_rewards(&mut a, pool.acc_reward_per_share).unwrap();
+        assert_eq!(a.pending_rewards, 1_000);
+        // Settling again without time passing pays nothing more
+        settle_lp_rewards(&mut a, pool.acc_reward_per_share).unwrap();
+        assert_eq!(a.pending_rewards, 1_000);
 
-#[derive(Accounts)]
-pub struct InsurancePoolContribution<'info> {
-    #[account(mut)]
-    pub contributor_token_account: Account<'info, TokenAccount>,
+        // A second staker with 3x the weight joins and takes 3/4 of later emissions
+        let b_weight = 300;
+        pool.total_weight += b_weight;
+        let b_debt = lp_reward_debt(b_weight, pool.acc_reward_per_share);
+        update_lp_pool(&mut pool, &controller, 200);
+        settle_lp_rewards(&mut a, pool.acc_reward_per_share).unwrap();
+        assert_eq!(a.pending_rewards, 1_250);
+        assert_eq!(lp_reward_debt(b_weight, pool.acc_reward_per_share) - b_debt, 750);
+    }
 
-    #[account(mut)]
-    pub insurance_pool_account: Account<'info, TokenAccount>,
+    #[test]
+    fn repricing_drops_the_boost_without_losing_earned_rewards() {
+        let mut pool = LpPool {
+            lp_mint: Pubkey::new_unique(),
+            vault: Pubkey::new_unique(),
+            alloc_point: 1,
+            acc_reward_per_share: 0,
+            total_weight: 0,
+            last_update: 0,
+            reserve_a: Pubkey::default(),
+            reserve_b: Pubkey::default(),
+            price_feed_a: Pubkey::default(),
+            price_feed_b: Pubkey::default(),
+            decimals_a: 6,
+            decimals_b: 6,
+        };
+        let controller = LpController { reward_per_second: 10, total_alloc_point: 1, pool_count: 1 };
+        let mut a = LpStakeAccount {
+            owner: Pubkey::new_unique(),
+            lp_mint: pool.lp_mint,
+            lp_staked: 100,
+            reward_multiplier: 0,
+            reward_debt: 0,
+            pending_rewards: 0,
+            last_claim_time: 0,
+        };
+        // Priced above the top tier, next to an unboosted stake of the same size
+        reweight_lp_stake(&mut a, &mut pool, 0, 2_000_000 * 1_000_000).unwrap();
+        pool.total_weight += 100;
+        assert_eq!((a.reward_multiplier, pool.total_weight), (5, 600));
 
-    #[account(mut)]
-    pub contributor_authority: Signer<'info>,
+        // The LP token loses its value; the boost is earned until the reprice, then dropped
+        update_lp_pool(&mut pool, &controller, 60);
+        settle_lp_rewards(&mut a, pool.acc_reward_per_share).unwrap();
+        let old_weight = a.weight();
+        reweight_lp_stake(&mut a, &mut pool, old_weight, 1_000 * 1_000_000).unwrap();
+        assert_eq!(a.pending_rewards, 500);
+        assert_eq!((a.reward_multiplier, pool.total_weight), (1, 200));
 
-    pub token_program: Program<'info, Token>,
-}
+        // From here on both stakes split emissions evenly
+        update_lp_pool(&mut pool, &controller, 120);
+        settle_lp_rewards(&mut a, pool.acc_reward_per_share).unwrap();
+        assert_eq!(a.pending_rewards, 800);
+    }
 
-#[derive(Accounts)]
-pub struct StakeLPTokens<'info> {
-    #[account(
-        init_if_needed,
-        payer = staker_authority,
-        space = 8 + 32 + 8 + 1,
-        seeds = [b"lp_stake", staker_authority.key().as_ref()],
-        bump
-    )]
-    pub lp_stake: Account<'info, LpStakeAccount>,
+    #[test]
+    fn lp_stakes_are_keyed_by_mint_and_owner() {
+        let owner = Pubkey::new_unique();
+        let (mint_a, mint_b) = (Pubkey::new_unique(), Pubkey::new_unique());
+        let lp_stake = |mint: &Pubkey| {
+            Pubkey::find_program_address(&[b"lp_stake", mint.as_ref(), owner.as_ref()], &crate::ID).0
+        };
+        let legacy = Pubkey::find_program_address(&[b"lp_stake", owner.as_ref()], &crate::ID).0;
+        assert_ne!(lp_stake(&mint_a), lp_stake(&mint_b));
+        assert_ne!(lp_stake(&mint_a), legacy);
+        assert_ne!(lp_stake(&mint_b), legacy);
+    }
 
-    #[account(mut)]
-    pub lp_token_account: Account<'info, TokenAccount>,
+    #[test]
+    fn legacy_lp_stake_parses_only_the_original_layout() {
+        let owner = Pubkey::new_unique();
+        let mut data = LpStakeAccount::DISCRIMINATOR.to_vec();
+        data.extend(owner.to_bytes());
+        data.extend(250_000u64.to_le_bytes());
+        data.push(2);
+        assert_eq!(data.len(), LEGACY_LP_STAKE_SPACE);
+        assert_eq!(parse_legacy_lp_stake(&data).unwrap(), (owner, 250_000));
 
-    #[account(mut)]
-    pub staking_pool_lp_account: Account<'info, TokenAccount>,
+        // A current-layout LP stake is not withdrawable through the legacy path
+        let current = LpStakeAccount {
+            owner,
+            lp_mint: Pubkey::new_unique(),
+            lp_staked: 250_000,
+            reward_multiplier: 2,
+            reward_debt: 0,
+            pending_rewards: 0,
+            last_claim_time: 0,
+        };
+        let mut current_data = Vec::new();
+        current.try_serialize(&mut current_data).unwrap();
+        assert!(parse_legacy_lp_stake(&current_data).is_err());
+        data[0] ^= 1;
+        assert!(parse_legacy_lp_stake(&data).is_err());
+    }
 
-    #[account(mut)]
-    pub staker_authority: Signer<'info>,
+    #[test]
+    fn legacy_withdrawals_pay_only_audited_balances() {
+        let (alice, bob) = (Pubkey::new_unique(), Pubkey::new_unique());
+        let alice_leaf = legacy_balance_leaf(b"legacy_lp", &alice, 250_000);
+        let bob_leaf = legacy_balance_leaf(b"legacy_lp", &bob, 40_000);
+        let root = if alice_leaf <= bob_leaf {
+            keccak::hashv(&[&alice_leaf, &bob_leaf]).to_bytes()
+        } else {
+            keccak::hashv(&[&bob_leaf, &alice_leaf]).to_bytes()
+        };
+        assert!(verify_merkle_proof(&[bob_leaf], root, alice_leaf));
+        // A stake's own inflated record, someone else's balance or another snapshot's leaf fail
+        assert!(!verify_merkle_proof(&[bob_leaf], root, legacy_balance_leaf(b"legacy_lp", &alice, 250_001)));
+        assert!(!verify_merkle_proof(&[alice_leaf], root, legacy_balance_leaf(b"legacy_lp", &alice, 40_000)));
+        assert!(!verify_merkle_proof(&[bob_leaf], root, legacy_balance_leaf(b"legacy_stake", &alice, 250_000)));
+    }
 
-    pub token_program: Program<'info, Token>,
-    pub system_program: Program<'info, System>,
-    pub rent: Sysvar<'info, Rent>,
-}
+    #[test]
+    fn legacy_nft_stakes_carry_over_only_escrowed_nfts() {
+        let (owner, mint, collection) = (Pubkey::new_unique(), Pubkey::new_unique(), Pubkey::new_unique());
+        let mut original = NFTStakeAccount::DISCRIMINATOR.to_vec();
+        original.extend(owner.to_bytes());
+        original.extend(mint.to_bytes());
+        original.push(1); // boosted, but the original program never escrowed the NFT
+        assert_eq!(original.len(), LEGACY_NFT_STAKE_SPACE);
+        assert_eq!(parse_legacy_nft_stake(&original).unwrap().1.map(|nft| nft.mint), None);
 
-#[derive(Accounts)]
-pub struct ClaimLPRewards<'info> {
-    #[account(
-        mut,
-        seeds = [b"lp_stake", staker_authority.key().as_ref()],
-        bump
-    )]
-    pub lp_stake: Account<'info, LpStakeAccount>,
+        let mut single = original.clone();
+        single.extend(collection.to_bytes());
+        single.push(2);
+        single.extend(1_500u16.to_le_bytes());
+        assert_eq!(single.len(), SINGLE_NFT_STAKE_SPACE);
+        let (parsed_owner, nft) = parse_legacy_nft_stake(&single).unwrap();
+        let nft = nft.unwrap();
+        assert_eq!((parsed_owner, nft.mint, nft.collection), (owner, mint, collection));
+        assert_eq!((nft.tier, nft.boost_bps, nft.boost_expires_at), (2, 1_500, 0));
 
-    #[account(mut)]
-    pub lp_reward_account: Account<'info, TokenAccount>,
+        // An unstaked single-NFT account carries nothing over
+        single[72] = 0;
+        assert!(parse_legacy_nft_stake(&single).unwrap().1.is_none());
 
-    #[account(
-        mut,
-        seeds = [b"global_state"],
-        bump = global_state.bump
-    )]
-    pub global_state: Account<'info, GlobalState>,
+        // The migrated account fits the current layout with every slot in use
+        let full = NFTStakeAccount { owner, nfts: vec![nft; MAX_STAKED_NFTS] };
+        let mut data = Vec::new();
+        full.try_serialize(&mut data).unwrap();
+        assert_eq!(data.len(), NFT_STAKE_SPACE);
+        assert!(parse_legacy_nft_stake(&data).is_err());
+    }
 
-    #[account(mut)]
-    pub mint: Account<'info, Mint>,
+    #[test]
+    fn legacy_stake_migrates_with_its_audited_amount() {
+        // A one-leaf snapshot: the root is the leaf itself
+        let owner = Pubkey::new_unique();
+        let root = legacy_balance_leaf(b"legacy_stake", &owner, 3_000);
+        assert!(verify_merkle_proof(&[], root, legacy_balance_leaf(b"legacy_stake", &owner, 3_000)));
+        // The amount the original program recorded (e.g. grown by auto-compounding) does not prove
+        assert!(!verify_merkle_proof(&[], root, legacy_balance_leaf(b"legacy_stake", &owner, 5_000)));
+        assert!(!verify_merkle_proof(&[], root, legacy_balance_leaf(b"legacy_lp", &owner, 3_000)));
+    }
 
-    pub staker_authority: Signer<'info>,
-    pub token_program: Program<'info, Token>,
-}
+    #[test]
+    fn legacy_accounts_grow_into_the_current_layout() {
+        // The original stake account, zero-extended as realloc does
+        let owner = Pubkey::new_unique();
+        let mut data = StakeAccount::DISCRIMINATOR.to_vec();
+        data.extend(owner.to_bytes());
+        data.extend(5_000u64.to_le_bytes());
+        data.extend(1_700_000_000i64.to_le_bytes());
+        data.push(1); // auto_compound
+        assert_eq!(data.len(), LEGACY_STAKE_ACCOUNT_SPACE);
+        data.resize(STAKE_ACCOUNT_SPACE, 0);
+        let staker = StakeAccount::try_deserialize(&mut &data[..]).unwrap();
+        assert_eq!((staker.owner, staker.amount, staker.stake_timestamp), (owner, 5_000, 1_700_000_000));
+        // The stale flag lands in last_claim_time, which the migration overwrites
+        assert_eq!(staker.last_claim_time, 1);
 
-#[derive(Accounts)]
-pub struct UpdateGovernance<'info> {
-    #[account(mut)]
-    pub governance: Account<'info, Governance>,
-    pub staker_authority: Signer<'info>,
-}
+        let mint = Pubkey::new_unique();
+        let mut data = GlobalState::DISCRIMINATOR.to_vec();
+        data.extend(mint.to_bytes());
+        data.push(254);
+        data.extend(42u64.to_le_bytes());
+        assert_eq!(data.len(), LEGACY_GLOBAL_STATE_SPACE);
+        data.resize(GLOBAL_STATE_SPACE, 0);
+        let global_state = GlobalState::try_deserialize(&mut &data[..]).unwrap();
+        assert_eq!((global_state.mint, global_state.bump, global_state.protocol_wide_volume), (mint, 254, 42));
+        assert_eq!(global_state.authority, Pubkey::default());
+    }
 
-// -----------------------------------------------
-//                State Structures
-// -----------------------------------------------
-#[account]
-pub struct GlobalState {
-    pub mint: Pubkey,
-    pub bump: u8,
-    pub protocol_wide_volume: u64, // track overall volume
-    // Add other global fields (e.g. dao_treasury Pubkey if needed)
-}
+    #[test]
+    fn lp_pool_updates_must_cover_every_pool_once() {
+        let (a, b, c) = (Pubkey::new_unique(), Pubkey::new_unique(), Pubkey::new_unique());
+        assert!(is_every_lp_pool(&[a, b, c], 3));
+        assert!(is_every_lp_pool(&[], 0));
+        // Omitting a pool would let its accrual jump to the new rate or split
+        assert!(!is_every_lp_pool(&[a, b], 3));
+        // Repeating one cannot stand in for a missing one
+        assert!(!is_every_lp_pool(&[a, b, b], 3));
+        assert!(!is_every_lp_pool(&[a, b, c], 2));
+    }
 
-#[account]
-pub struct TraderVolume {
-    pub trader: Pubkey,
-    pub total_volume: u64,
-    pub last_trade_time: i64,
-}
+    #[test]
+    fn lp_emissions_split_by_alloc_points() {
+        assert_eq!(lp_pool_emission(100, 10, 3, 4), 750);
+        assert_eq!(lp_pool_emission(100, 10, 1, 4), 250);
+        assert_eq!(lp_pool_emission(100, 10, 0, 4), 0);
+        assert_eq!(lp_pool_emission(100, 10, 0, 0), 0);
+    }
 
-#[account]
-pub struct StakeAccount {
-    pub owner: Pubkey,
-    pub amount: u64,
-    pub stake_timestamp: i64,
-    pub auto_compound: bool,
-}
+    #[test]
+    fn gauge_lists_name_each_lp_pool_at_most_once() {
+        let (a, b, c) = (Pubkey::new_unique(), Pubkey::new_unique(), Pubkey::new_unique());
+        assert!(is_valid_gauge_list(&[a, b], &[a, b, c]));
+        assert!(is_valid_gauge_list(&[], &[a]));
+        // A repeated mint or one without a pool would leave the rollover unable to match them
+        assert!(!is_valid_gauge_list(&[a, a], &[a, b]));
+        assert!(!is_valid_gauge_list(&[a, Pubkey::new_unique()], &[a, b]));
+    }
 
-#[account]
-pub struct NFTStakeAccount {
-    pub owner: Pubkey,
-    pub nft_minted: Pubkey,
-    pub boosted: bool,
-}
+    #[test]
+    fn gauge_votes_split_by_bps() {
+        assert_eq!(gauge_vote_split(1_000, &[5_000, 2_500, 0]), vec![500, 250, 0]);
+        assert_eq!(gauge_vote_split(3, &[3_333, 6_667]), vec![0, 2]);
+    }
 
-#[account]
-pub struct VestingSchedule {
-    pub owner: Pubkey,
-    pub total_reward: u64,
-    pub claimed: u64,
-    pub start_time: i64,
-    pub duration: i64,
-}
+    #[test]
+    fn fair_lp_value_resists_reserve_skew() {
+        // 1_000 SOL at $150 (9 decimals, expo -8) is $150_000
+        let sol = usd_value(1_000_000_000_000, 9, 15_000_000_000, -8).unwrap();
+        assert_eq!(sol, 150_000_000_000);
+        // Balanced pool: fair value equals the sum of both legs
+        assert_eq!(fair_lp_pool_value(sol, sol), Some(300_000_000_000));
+        // Reserves pushed 4:1 by a large swap: the naive sum reads $375k, fair value stays $300k
+        assert_eq!(fair_lp_pool_value(sol * 2, sol / 2), Some(300_000_000_000));
 
-#[account]
-pub struct LpStakeAccount {
-    pub owner: Pubkey,
-    pub lp_staked: u64,
-    pub reward_multiplier: u8,
-}
+        assert_eq!(lp_reward_multiplier(50_000 * 1_000_000), 1);
+        assert_eq!(lp_reward_multiplier(150_000 * 1_000_000), 2);
+        assert_eq!(lp_reward_multiplier(2_000_000 * 1_000_000), 5);
+    }
 
-#[account]
-pub struct Governance {
-    pub total_votes: u64,
-    pub reward_percentage: u8, // base reward percentage
-    pub lp_boost: u8,          // additional boost for LP rewards
-    pub dao_approved: bool,    // indicates a DAO vote approval
-}
+    #[test]
+    fn stake_reward_follows_governance_parameters() {
+        let week = 7 * 24 * 60 * 60;
+        let year = SECONDS_PER_YEAR;
+        // 10% a year, 1.0x ve boost, no NFT
+        assert_eq!(stake_reward(1_000, week, year, 10, 10_000, 0), (100, 0));
+        // Governance raises the base to 20%
+        assert_eq!(stake_reward(1_000, week, year, 20, 10_000, 0), (200, 0));
+        // Early claim: 10% of the reward goes to the treasury
+        assert_eq!(stake_reward(1_000, week - 1, year, 20, 10_000, 0), (180, 20));
+        assert_eq!(apply_lp_boost(1_000, 0), 1_000);
+        assert_eq!(apply_lp_boost(1_000, 10), 1_100);
+    }
 
-// -----------------------------------------------
-//                    Errors
-// -----------------------------------------------
-#[error_code]
-pub enum CustomError {
-    #[msg("Flash Loan Attack Detected!")]
-    FlashLoanDetected,
-    #[msg("Trade Price Out of Expected Range")]
-    InvalidTradePrice,
-    #[msg("Early unstake is not allowed")]
-    EarlyUnstakePenalty,
-    #[msg("Nothing to claim in vesting schedule")]
-    NothingToClaim,
-    #[msg("Invalid reward or LP boost parameters")]
-    InvalidRewardParameters,
-    #[msg("Oracle error occurred")]
-    OracleError,
-    #[msg("Pyth price feed is negative")]
-    NegativePythPrice,
-    #[msg("Failed converting i64 to u64")]
-    ConversionError,
-    #[msg("Overflow in checked arithmetic")]
-    ArithmeticOverflow,
-    #[msg("Insufficient unique traders for anti-flashbot")]
-    InsufficientUniqueTraders,
-    #[msg("NFT floor price too low to stake")]
-    NFTFloorTooLow,
-    #[msg("Governance not approved")]
-    GovernanceNotApproved,
+    #[test]
+    fn buyback_rejects_protocol_token_accounts() {
+        let global_state = Pubkey::new_unique();
+        let token_account = |owner: Pubkey| {
+            let mut data = vec![0u8; 165];
+            data[..32].copy_from_slice(&Pubkey::new_unique().to_bytes());
+            data[32..64].copy_from_slice(&owner.to_bytes());
+            data
+        };
+        let vault = token_account(global_state);
+        assert!(is_token_account_owned_by(&anchor_spl::token::ID, &vault, &global_state));
+        assert!(is_token_account_owned_by(&spl_token_2022::ID, &vault, &global_state));
+        // The AMM's own pool accounts and the buyback authority's accounts pass
+        let buyback_authority = Pubkey::find_program_address(&[b"buyback_authority"], &crate::ID).0;
+        assert!(!is_token_account_owned_by(&anchor_spl::token::ID, &token_account(buyback_authority), &global_state));
+        // Only token-program accounts are parsed, and mints are too short to match
+        assert!(!is_token_account_owned_by(&Pubkey::new_unique(), &vault, &global_state));
+        assert!(!is_token_account_owned_by(&anchor_spl::token::ID, &vault[..82], &global_state));
+    }
+
+    #[test]
+    fn buyback_min_out_follows_oracle_price() {
+        // 0.25 USDC per multiYIELD (price 25_000_000, expo -8), both 6 decimals:
+        // 100 USDC should buy 400 multiYIELD, 1% slippage -> 396
+        assert_eq!(buyback_min_out(100_000_000, 6, 6, 25_000_000, -8, 100), Some(396_000_000));
+        // 9-decimal quote token, price 2.0 (expo 0)
+        assert_eq!(buyback_min_out(4_000_000_000, 9, 6, 2, 0, 0), Some(2_000_000));
+        assert_eq!(buyback_min_out(1, 6, 6, 0, 0, 0), None);
+    }
 }
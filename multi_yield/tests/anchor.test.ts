@@ -11,6 +11,11 @@ describe("multi_yield Tests", () => {
   let stakerPda;
   let stakerBump;
 
+  let governancePda;
+  let stakingPoolPda;
+  let feeRegistryPda;
+  let stakerFeeClaimPda;
+
   // Example placeholders for addresses. 
   let mintPubkey;
   let insurancePoolAccount;
@@ -41,6 +46,28 @@ describe("multi_yield Tests", () => {
       pg.program.programId
     );
 
+    //  Derive the canonical governance PDA
+    [governancePda] = await web3.PublicKey.findProgramAddress(
+      [Buffer.from("governance")],
+      pg.program.programId
+    );
+
+    //  Derive the staking pool backing bonded stake, positions and veMY locks
+    [stakingPoolPda] = await web3.PublicKey.findProgramAddress(
+      [Buffer.from("staking_pool")],
+      pg.program.programId
+    );
+
+    //  Derive the fee accumulator registry and the bonded stake's fee claim
+    [feeRegistryPda] = await web3.PublicKey.findProgramAddress(
+      [Buffer.from("fee_registry")],
+      pg.program.programId
+    );
+    [stakerFeeClaimPda] = await web3.PublicKey.findProgramAddress(
+      [Buffer.from("fee_claim"), stakerPda.toBuffer()],
+      pg.program.programId
+    );
+
     //  Fill in actual addresses
      mintPubkey = new web3.PublicKey("...someMintPubkey...");
     insurancePoolAccount = new web3.PublicKey("...insurancePoolAccount...");
@@ -56,6 +83,8 @@ describe("multi_yield Tests", () => {
       .initialize(globalStateBump)
       .accounts({
         globalState: globalStatePda,
+        governance: governancePda,
+        feeRegistry: feeRegistryPda,
         mint: mintPubkey,
         user: pg.wallet.publicKey,
         systemProgram: web3.SystemProgram.programId, 
@@ -71,6 +100,7 @@ describe("multi_yield Tests", () => {
     const globalState = await pg.program.account.globalState.fetch(globalStatePda);
     console.log("GlobalState data:", globalState);
     assert.equal(globalState.bump, globalStateBump, "GlobalState bump mismatch");
+    assert.ok(globalState.governance.equals(governancePda), "Governance PDA not linked");
   });
 
   it("rewardTrade", async () => {
@@ -99,24 +129,27 @@ describe("multi_yield Tests", () => {
     assert.equal(traderVolume.totalVolume.toString(), "10000", "trade_amount mismatch");
   });
 
-  it("stakeTokens", async () => {
+  it("bondTokens", async () => {
     const stakeAmount = new BN(5000);
-    const autoCompound = true;
 
     const txHash = await pg.program.methods
-      .stakeTokens(stakeAmount, autoCompound)
+      .bondTokens(stakeAmount)
       .accounts({
         staker: stakerPda,
         stakerTokenAccount: stakerTokenAccount,
-        stakingPoolTokenAccount: new web3.PublicKey("...stakingPoolAcct..."),
+        stakingPoolTokenAccount: stakingPoolPda,
+        globalState: globalStatePda,
+        mint: mintPubkey,
         stakerAuthority: pg.wallet.publicKey,
+        feeRegistry: feeRegistryPda,
+        feeClaim: stakerFeeClaimPda,
         tokenProgram: new web3.PublicKey("TokenkegQfeZyiNwAJbNbGKPFXCWuBvf9Ss623VQ5DA"), 
         systemProgram: web3.SystemProgram.programId, 
         rent: web3.SYSVAR_RENT_PUBKEY, 
       })
       .rpc();
 
-    console.log("stakeTokens() tx:", txHash);
+    console.log("bondTokens() tx:", txHash);
     await pg.connection.confirmTransaction(txHash);
 
     const stakerAccount = await pg.program.account.stakeAccount.fetch(stakerPda);
@@ -131,9 +164,13 @@ describe("multi_yield Tests", () => {
         staker: stakerPda,
         stakerRewardAccount: stakerRewardAccount,
         globalState: globalStatePda,
+        governance: governancePda,
         mint: mintPubkey,
         nftStake: nftStakePda,
+        veLock: null,
+        rewardVault: null,
         daoTreasuryAccount: daoTreasuryTokenAccount,
+        authority: pg.wallet.publicKey,
         tokenProgram: new web3.PublicKey("TokenkegQfeZyiNwAJbNbGKPFXCWuBvf9Ss623VQ5DA"), 
       })
       .rpc();
@@ -144,4 +181,78 @@ describe("multi_yield Tests", () => {
     const stakerAccount = await pg.program.account.stakeAccount.fetch(stakerPda);
     console.log("StakerAccount data after claim:", stakerAccount);
   });
+
+  it("stakeToPosition", async () => {
+    const positionId = new BN(1);
+    const [positionPda] = await web3.PublicKey.findProgramAddress(
+      [
+        Buffer.from("stake_position"),
+        pg.wallet.publicKey.toBuffer(),
+        positionId.toArrayLike(Buffer, "le", 8),
+      ],
+      pg.program.programId
+    );
+    const [positionFeeClaimPda] = await web3.PublicKey.findProgramAddress(
+      [Buffer.from("fee_claim"), positionPda.toBuffer()],
+      pg.program.programId
+    );
+
+    const txHash = await pg.program.methods
+      .stakeToPosition(positionId, new BN(2000), new BN(30 * 24 * 60 * 60), false)
+      .accounts({
+        position: positionPda,
+        stakerTokenAccount: stakerTokenAccount,
+        stakingPoolTokenAccount: stakingPoolPda,
+        globalState: globalStatePda,
+        nftStake: null,
+        mint: mintPubkey,
+        stakerAuthority: pg.wallet.publicKey,
+        feeRegistry: feeRegistryPda,
+        feeClaim: positionFeeClaimPda,
+        tokenProgram: new web3.PublicKey("TokenkegQfeZyiNwAJbNbGKPFXCWuBvf9Ss623VQ5DA"),
+        systemProgram: web3.SystemProgram.programId,
+        rent: web3.SYSVAR_RENT_PUBKEY,
+      })
+      .rpc();
+
+    console.log("stakeToPosition() tx:", txHash);
+    await pg.connection.confirmTransaction(txHash);
+
+    const position = await pg.program.account.stakePosition.fetch(positionPda);
+    console.log("StakePosition data:", position);
+    assert.equal(position.amount.toString(), "2000", "Position amount mismatch");
+  });
+
+  it("requestUnstake", async () => {
+    const ticketId = new BN(0);
+    const [ticketPda] = await web3.PublicKey.findProgramAddress(
+      [
+        Buffer.from("unstake_ticket"),
+        pg.wallet.publicKey.toBuffer(),
+        ticketId.toArrayLike(Buffer, "le", 8),
+      ],
+      pg.program.programId
+    );
+
+    const txHash = await pg.program.methods
+      .requestUnstake(ticketId, new BN(1000))
+      .accounts({
+        staker: stakerPda,
+        unstakeTicket: ticketPda,
+        globalState: globalStatePda,
+        feeRegistry: feeRegistryPda,
+        feeClaim: stakerFeeClaimPda,
+        stakerAuthority: pg.wallet.publicKey,
+        systemProgram: web3.SystemProgram.programId,
+      })
+      .rpc();
+
+    console.log("requestUnstake() tx:", txHash);
+    await pg.connection.confirmTransaction(txHash);
+
+    const ticket = await pg.program.account.unstakeTicket.fetch(ticketPda);
+    console.log("UnstakeTicket data:", ticket);
+    assert.equal(ticket.amount.toString(), "1000", "Ticket amount mismatch");
+    assert.ok(ticket.claimableAt.gt(ticket.requestedAt), "Ticket should wait for cooldown");
+  });
 });